@@ -0,0 +1,70 @@
+//! Embeds the release version from version.toml, and the git commit rufio
+//! was built from when built from a checkout.
+#![allow(clippy::print_stdout)]
+
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-changed=version.toml");
+    watch_head();
+
+    // Cargo.toml carries a placeholder; the released version lives in version.toml
+    let version =
+        release_version().unwrap_or_else(|| std::env::var("CARGO_PKG_VERSION").unwrap_or_default());
+    println!("cargo:rustc-env=RUFIO_VERSION={}", version);
+
+    let commit = Command::new("git")
+        .args(["rev-parse", "--short", "HEAD"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+        .filter(|commit| !commit.is_empty());
+    if let Some(commit) = commit {
+        println!("cargo:rustc-env=RUFIO_GIT_COMMIT={}", commit);
+    }
+}
+
+/// The `version = "..."` value from version.toml, if present
+fn release_version() -> Option<String> {
+    let content = fs::read_to_string("version.toml").ok()?;
+    content.lines().find_map(|line| {
+        let (key, value) = line.split_once('=')?;
+        (key.trim() == "version")
+            .then(|| value.trim().trim_matches('"').to_string())
+            .filter(|version| !version.is_empty())
+    })
+}
+
+/// Rebuild when HEAD moves, whether by checkout or by a new commit on the
+/// branch. Only existing paths are watched: cargo reruns the script on every
+/// build for a path that doesn't exist, e.g. when building from a tarball.
+fn watch_head() {
+    let git_dir = Path::new(".git");
+    let Ok(head) = fs::read_to_string(git_dir.join("HEAD")) else {
+        return;
+    };
+    watch(&git_dir.join("HEAD"));
+
+    let Some(reference) = head.trim().strip_prefix("ref: ") else {
+        return;
+    };
+    let ref_file = git_dir.join(reference);
+    if ref_file.exists() {
+        watch(&ref_file);
+    } else if let Some(ref_dir) = ref_file.parent().filter(|dir| dir.exists()) {
+        // A packed ref gets a loose file on its next commit
+        watch(ref_dir);
+    }
+    let packed_refs = git_dir.join("packed-refs");
+    if packed_refs.exists() {
+        watch(&packed_refs);
+    }
+}
+
+fn watch(path: &Path) {
+    println!("cargo:rerun-if-changed={}", path.display());
+}
@@ -1,3 +1,5 @@
 pub mod runner;
 
-pub use runner::run_checks;
+pub use runner::{
+    describe_requirement, explain_file, run_checks, CheckContext, CheckResult, Trigger,
+};
@@ -1,13 +1,66 @@
-use crate::config::{Check, LoadedConfig};
+use crate::config::{dependency_order, Check, HookEvent, Level, LoadedConfig, MatchMode};
+use crate::parse_porcelain_line;
 use crate::transcript::ToolUseEvent;
-use glob::Pattern;
+use glob::{MatchOptions, Pattern};
+use semver::Version;
+use serde::ser::{Serialize, SerializeStruct, Serializer};
+use std::fs;
 use std::path::Path;
+use std::process::Command;
+use tracing::warn;
+
+/// Glob options for paths_changed: `*` never crosses a directory separator
+const PATH_MATCH_OPTIONS: MatchOptions = MatchOptions {
+    case_sensitive: true,
+    require_literal_separator: true,
+    require_literal_leading_dot: false,
+};
 
 /// Result of running a single check
 #[derive(Debug)]
 pub struct CheckResult {
     pub check_name: String,
     pub reason: Option<String>,
+    /// Whether a failure blocks or is only reported
+    pub level: Level,
+}
+
+impl CheckResult {
+    fn pass(check: &Check) -> Self {
+        CheckResult {
+            check_name: check.name.clone(),
+            reason: None,
+            level: check.level,
+        }
+    }
+
+    /// A failure that should stop Claude from finishing
+    pub fn blocks(&self) -> bool {
+        self.reason.is_some() && self.level == Level::Block
+    }
+}
+
+/// Serializes as `{ "check": name, "passed": bool, "reason": string|null, "level": "block"|"warn" }`
+impl Serialize for CheckResult {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("CheckResult", 4)?;
+        state.serialize_field("check", &self.check_name)?;
+        state.serialize_field("passed", &self.reason.is_none())?;
+        state.serialize_field("reason", &self.reason)?;
+        state.serialize_field("level", &self.level)?;
+        state.end()
+    }
+}
+
+/// Session-wide facts shared by every check in an evaluation
+#[derive(Debug, Default)]
+pub struct CheckContext<'a> {
+    /// Current git branch, if known
+    pub branch: Option<&'a str>,
+    /// Turn number of the most recent user prompt
+    pub current_turn: usize,
+    /// The hook being handled; checks not listed `on` it are skipped
+    pub hook: HookEvent,
 }
 
 /// Run all checks from a loaded config against changed files.
@@ -17,15 +70,45 @@ pub fn run_checks(
     changed_files: &[String],
     events: &[ToolUseEvent],
     repo_root: &Path,
+    context: &CheckContext,
 ) -> Vec<CheckResult> {
-    let mut results = Vec::new();
+    let checks = &loaded.config.checks;
+    // Cycles are rejected at load time; fall back to config order regardless
+    let order = dependency_order(checks).unwrap_or_else(|_| (0..checks.len()).collect());
+
+    let mut outcomes: Vec<Option<CheckResult>> = checks.iter().map(|_| None).collect();
+    let mut failed = vec![false; checks.len()];
+    for index in order {
+        let check = &checks[index];
+        if !check.on.contains(&context.hook) {
+            continue;
+        }
+        let dependency_failed = check.depends_on.iter().any(|dep| {
+            checks
+                .iter()
+                .zip(&failed)
+                .any(|(c, &failed)| failed && &c.name == dep)
+        });
+        if dependency_failed {
+            // Skip it: the dependency's failure is the actionable one
+            failed[index] = true;
+            continue;
+        }
 
-    for check in &loaded.config.checks {
-        let result = run_single_check(check, &loaded.config_dir, changed_files, events, repo_root);
-        results.push(result);
+        let result = run_single_check(
+            check,
+            check.config_dir(&loaded.config_dir),
+            changed_files,
+            events,
+            repo_root,
+            context,
+        );
+        failed[index] = result.reason.is_some();
+        outcomes[index] = Some(result);
     }
 
-    results
+    // Report in config order
+    outcomes.into_iter().flatten().collect()
 }
 
 /// Run a single check against the changed files
@@ -35,64 +118,188 @@ fn run_single_check(
     changed_files: &[String],
     events: &[ToolUseEvent],
     repo_root: &Path,
+    context: &CheckContext,
 ) -> CheckResult {
-    // Check path_exists condition first
+    // Check branch conditions first
+    if !branch_matches(check, context.branch) {
+        return CheckResult::pass(check);
+    }
+
+    // Check path_exists condition
     if let Some(path_exists) = &check.when.path_exists {
-        let required_path = config_dir.join(path_exists);
-        if !required_path.exists() {
-            return CheckResult {
-                check_name: check.name.clone(),
-                reason: None,
-            };
+        if !path_exists_matches(path_exists, config_dir) {
+            return CheckResult::pass(check);
         }
     }
 
-    // Parse the glob pattern
-    let pattern = match Pattern::new(&check.when.paths_changed) {
-        Ok(p) => p,
-        Err(_) => {
-            return CheckResult {
-                check_name: check.name.clone(),
-                reason: Some(format!(
-                    "Invalid glob pattern '{}' in check '{}'",
-                    check.when.paths_changed, check.name
-                )),
-            };
+    // Parse the glob patterns (validated at load, so only hand-built checks can fail here)
+    let mut patterns = Vec::with_capacity(check.when.paths_changed.len());
+    for glob in &check.when.paths_changed {
+        match Pattern::new(glob) {
+            Ok(p) => patterns.push(p),
+            Err(_) => {
+                return CheckResult {
+                    check_name: check.name.clone(),
+                    reason: Some(format!(
+                        "Invalid glob pattern '{}' in check '{}'",
+                        glob, check.name
+                    )),
+                    level: check.level,
+                };
+            }
         }
-    };
+    }
 
     // Find matching files (make paths relative to config dir before matching)
     let matching_files: Vec<&String> = changed_files
         .iter()
-        .filter(|f| file_matches_relative(f, &pattern, config_dir, repo_root))
+        .filter(|f| file_matches_relative(f, &patterns, config_dir, repo_root))
         .collect();
 
     if matching_files.is_empty() {
-        return CheckResult {
-            check_name: check.name.clone(),
-            reason: None,
-        };
+        return CheckResult::pass(check);
     }
 
+    let events = if check.then.current_turn_only {
+        current_turn_events(events, context.current_turn)
+    } else {
+        events
+    };
+
     // Dispatch to the appropriate check type
     if let Some(commands) = &check.then.ensure_commands {
-        check_ensure_commands(check, &pattern, commands, events, config_dir)
+        check_ensure_commands(check, &patterns, commands, events, config_dir, repo_root)
     } else if let Some(paths) = &check.then.ensure_changed {
+        if check.then.skip_trivial_changes && diff_is_trivial(&matching_files, repo_root) {
+            return CheckResult::pass(check);
+        }
         check_ensure_changed(check, paths, changed_files, config_dir, repo_root)
+    } else if let Some(commands) = &check.then.forbid_commands {
+        check_forbid_commands(check, commands, events)
+    } else if let Some(paths) = &check.then.ensure_no_changes {
+        check_ensure_no_changes(check, paths, changed_files, config_dir, repo_root)
+    } else if let Some(paths) = &check.then.require_clean {
+        check_require_clean(check, paths, config_dir)
+    } else if let Some(commands) = &check.then.format_commands {
+        check_format_commands(check, &patterns, commands, events, config_dir, repo_root)
+    } else if let Some(paths) = &check.then.ensure_exists {
+        check_ensure_exists(check, paths, config_dir)
     } else {
-        CheckResult {
-            check_name: check.name.clone(),
-            reason: None,
+        CheckResult::pass(check)
+    }
+}
+
+/// Whether a check's `when` conditions trigger for a single changed file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Trigger {
+    Triggers,
+    /// The file doesn't match any paths_changed glob
+    NoPathMatch,
+    /// The when.path_exists condition isn't met
+    PathMissing,
+    /// A paths_changed glob doesn't compile
+    InvalidGlob(String),
+}
+
+/// Evaluate the path conditions of a check against one file (relative to
+/// repo root, or absolute). Branch conditions are not considered.
+pub fn explain_file(check: &Check, file: &str, config_dir: &Path, repo_root: &Path) -> Trigger {
+    if let Some(path_exists) = &check.when.path_exists {
+        if !path_exists_matches(path_exists, config_dir) {
+            return Trigger::PathMissing;
+        }
+    }
+
+    let mut patterns = Vec::with_capacity(check.when.paths_changed.len());
+    for glob in &check.when.paths_changed {
+        match Pattern::new(glob) {
+            Ok(p) => patterns.push(p),
+            Err(_) => return Trigger::InvalidGlob(glob.clone()),
+        }
+    }
+
+    if file_matches_relative(file, &patterns, config_dir, repo_root) {
+        Trigger::Triggers
+    } else {
+        Trigger::NoPathMatch
+    }
+}
+
+/// Describe what a check requires once triggered
+pub fn describe_requirement(check: &Check) -> String {
+    let then = &check.then;
+    if let Some(commands) = &then.ensure_commands {
+        if then.ordered {
+            format!("run in order after editing: {}", commands.join(" -> "))
+        } else {
+            format!("run after editing: {}", commands.join(", "))
+        }
+    } else if let Some(paths) = &then.ensure_changed {
+        format!("change one of: {}", paths.join(", "))
+    } else if let Some(commands) = &then.forbid_commands {
+        format!("never run: {}", commands.join(", "))
+    } else if let Some(paths) = &then.ensure_no_changes {
+        format!("leave unchanged: {}", paths.join(", "))
+    } else if let Some(paths) = &then.require_clean {
+        format!("leave clean in git: {}", paths.join(", "))
+    } else if let Some(commands) = &then.format_commands {
+        format!("run last, with no edits after: {}", commands.join(", "))
+    } else if let Some(paths) = &then.ensure_exists {
+        format!("keep existing: {}", paths.join(", "))
+    } else {
+        "nothing".to_string()
+    }
+}
+
+/// The events since the most recent user prompt.
+/// Events are in transcript order, so turns never decrease.
+fn current_turn_events(events: &[ToolUseEvent], current_turn: usize) -> &[ToolUseEvent] {
+    let start = events.partition_point(|e| e.turn < current_turn);
+    &events[start..]
+}
+
+/// Check the when.branch / when.skip_branches conditions against the current branch.
+/// A check restricted to a branch never applies when the branch is unknown.
+fn branch_matches(check: &Check, branch: Option<&str>) -> bool {
+    let branch_glob_matches =
+        |glob: &str, branch: &str| Pattern::new(glob).is_ok_and(|p| p.matches(branch));
+
+    if let Some(required) = &check.when.branch {
+        if !branch.is_some_and(|b| branch_glob_matches(required, b)) {
+            return false;
+        }
+    }
+
+    if let (Some(skipped), Some(branch)) = (&check.when.skip_branches, branch) {
+        if skipped.iter().any(|glob| branch_glob_matches(glob, branch)) {
+            return false;
         }
     }
+
+    true
+}
+
+/// Check if a path_exists condition (relative to config dir) is satisfied.
+/// Literal paths are checked directly; globs need at least one match on disk.
+fn path_exists_matches(path_exists: &str, config_dir: &Path) -> bool {
+    if !path_exists.contains(['*', '?', '[']) {
+        return config_dir.join(path_exists).exists();
+    }
+
+    let base = Pattern::escape(&config_dir.to_string_lossy());
+    let full_pattern = format!("{}/{}", base, path_exists);
+    match glob::glob(&full_pattern) {
+        Ok(mut paths) => paths.any(|p| p.is_ok()),
+        Err(_) => false,
+    }
 }
 
-/// Check if a file (relative to repo root) matches a glob pattern
-/// after converting to be relative to config dir.
+/// Check if a file (relative to repo root, or absolute) matches any of the
+/// glob patterns after converting to be relative to config dir.
 /// Files outside the config directory are skipped.
 fn file_matches_relative(
     file_path: &str,
-    pattern: &Pattern,
+    patterns: &[Pattern],
     config_dir: &Path,
     repo_root: &Path,
 ) -> bool {
@@ -102,83 +309,345 @@ fn file_matches_relative(
         Err(_) => return false, // File is outside config dir
     };
 
-    let relative_str = relative.to_string_lossy();
-    pattern.matches(relative_str.as_ref())
+    patterns.iter().any(|p| glob_matches(p, relative))
 }
 
-/// Check if a transcript file path (absolute) matches a glob pattern
-/// relative to config dir.
-fn transcript_path_matches(path: &str, pattern: &Pattern, config_dir: &Path) -> bool {
-    let absolute = Path::new(path);
-    let relative = match absolute.strip_prefix(config_dir) {
-        Ok(r) => r,
-        Err(_) => return false,
+/// Match a config-relative path against a paths_changed glob.
+/// Wildcard patterns without a `/` (like `*.rs`) match the file name at any
+/// depth; everything else, including bare names like `Cargo.toml`, is
+/// anchored and must match the whole path.
+fn glob_matches(pattern: &Pattern, relative: &Path) -> bool {
+    let raw = pattern.as_str();
+    if raw.contains('/') || !raw.contains(['*', '?', '[']) {
+        pattern.matches_path_with(relative, PATH_MATCH_OPTIONS)
+    } else {
+        relative
+            .file_name()
+            .is_some_and(|name| pattern.matches_with(&name.to_string_lossy(), PATH_MATCH_OPTIONS))
+    }
+}
+
+/// Build a failing result. A custom then.message replaces the default reason,
+/// with any `{missing}` placeholder substituted by the unmet items.
+fn failure(check: &Check, default_reason: String, missing: &str) -> CheckResult {
+    let reason = match &check.then.message {
+        Some(message) => message.replace("{missing}", missing),
+        None => default_reason,
+    };
+
+    CheckResult {
+        check_name: check.name.clone(),
+        reason: Some(reason),
+        level: check.level,
+    }
+}
+
+/// Check whether a Bash command satisfies a required command, either
+/// directly or through one of the check's configured aliases for it.
+fn command_satisfies(check: &Check, required: &str, command: &str) -> bool {
+    command_offset(check, required, command, 0).is_some()
+}
+
+/// Byte offset of the first place at or after `from` where a Bash command
+/// satisfies a required command, directly or through an alias.
+fn command_offset(check: &Check, required: &str, command: &str, from: usize) -> Option<usize> {
+    if check.then.ignore_background && is_backgrounded(command) {
+        return None;
+    }
+
+    let aliases = check
+        .then
+        .command_aliases
+        .as_ref()
+        .and_then(|aliases| aliases.get(required))
+        .map(Vec::as_slice)
+        .unwrap_or_default();
+
+    std::iter::once(required)
+        .chain(aliases.iter().map(String::as_str))
+        .filter_map(|candidate| {
+            command_offsets(check.then.match_mode, candidate, command)
+                .into_iter()
+                .find(|&pos| {
+                    pos >= from
+                        && match &check.when.cwd_prefix {
+                            Some(dir) => runs_in_dir(&command[..pos], dir),
+                            None => true,
+                        }
+                })
+        })
+        .min()
+}
+
+/// Byte offset in `command` where `wanted` first runs, if it does.
+fn find_command(mode: MatchMode, wanted: &str, command: &str) -> Option<usize> {
+    command_offsets(mode, wanted, command).first().copied()
+}
+
+/// Byte offsets in `command` where `wanted` runs, in order.
+/// With MatchMode::Tokens each is the start of a step (split on `;`, `&`,
+/// `|` and newlines) containing every word of `wanted`.
+fn command_offsets(mode: MatchMode, wanted: &str, command: &str) -> Vec<usize> {
+    match mode {
+        MatchMode::Substring => command.match_indices(wanted).map(|(pos, _)| pos).collect(),
+        MatchMode::Tokens => {
+            let mut offsets = Vec::new();
+            let mut start = 0;
+            let ends = command
+                .char_indices()
+                .filter(|(_, c)| matches!(c, ';' | '&' | '|' | '\n'))
+                .chain(std::iter::once((command.len(), ';')));
+            for (end, separator) in ends {
+                let step: Vec<&str> = command[start..end].split_whitespace().collect();
+                if !step.is_empty() && wanted.split_whitespace().all(|t| step.contains(&t)) {
+                    offsets.push(start);
+                }
+                start = end + separator.len_utf8();
+            }
+            offsets
+        }
+    }
+}
+
+/// Whether the shell text before a command leaves it running in `dir`:
+/// the last `cd` must target `dir`, either as written or as an absolute
+/// path ending in it. Without any `cd` only `.` matches.
+fn runs_in_dir(preceding: &str, dir: &str) -> bool {
+    let normalize = |path: &str| {
+        let path = path.trim_matches(['"', '\'']);
+        let path = path.strip_prefix("./").unwrap_or(path);
+        let path = path.trim_end_matches('/');
+        if path.is_empty() {
+            ".".to_string()
+        } else {
+            path.to_string()
+        }
     };
+    let dir = normalize(dir);
+
+    let last_cd = preceding
+        .split(['&', ';', '|', '\n', '('])
+        .filter_map(|step| step.trim().strip_prefix("cd "))
+        .next_back();
+
+    match last_cd {
+        Some(target) => {
+            let target = normalize(target.trim());
+            target == dir || (target.starts_with('/') && target.ends_with(&format!("/{}", dir)))
+        }
+        None => dir == ".",
+    }
+}
+
+/// Whether a command obviously ran in the background: it ends with a lone,
+/// unescaped `&`, or its last step is `disown`. Deliberately conservative;
+/// anything less obvious counts as having run.
+fn is_backgrounded(command: &str) -> bool {
+    let trimmed = command.trim_end();
 
-    let relative_str = relative.to_string_lossy();
-    pattern.matches(relative_str.as_ref())
+    if let Some(before) = trimmed.strip_suffix('&') {
+        // `&&` is a chain, `\&` is escaped, `>&`/`|&` are redirections
+        return !before.ends_with(['&', '\\', '>', '|']);
+    }
+
+    let last_step = trimmed
+        .rsplit(['&', ';', '|', '\n'])
+        .next()
+        .unwrap_or(trimmed)
+        .trim();
+    last_step == "disown" || last_step.starts_with("disown ")
 }
 
 /// Check that required commands were run after the last matching edit
 fn check_ensure_commands(
     check: &Check,
-    pattern: &Pattern,
+    patterns: &[Pattern],
     required_commands: &[String],
     events: &[ToolUseEvent],
     config_dir: &Path,
+    repo_root: &Path,
 ) -> CheckResult {
     // Find the index of the last matching file write
     let last_write_idx = events.iter().rposition(|e| {
-        (e.tool_name == "Edit" || e.tool_name == "Write")
+        e.is_edit()
             && e.file_path
                 .as_ref()
-                .is_some_and(|p| transcript_path_matches(p, pattern, config_dir))
+                .is_some_and(|p| file_matches_relative(p, patterns, config_dir, repo_root))
     });
 
     // If no matching file was edited in this session, skip the check
     let last_write_idx = match last_write_idx {
         Some(idx) => idx,
         None => {
-            return CheckResult {
-                check_name: check.name.clone(),
-                reason: None,
-            };
+            return CheckResult::pass(check);
         }
     };
 
-    // Find the event index (not vec position) of the last write
-    let last_write_event_idx = events[last_write_idx].index;
+    // Commands count from the event (not vec position) after the last write
+    let after_write = (events[last_write_idx].index + 1, 0);
+
+    if check.then.ordered {
+        return check_commands_in_order(check, required_commands, events, after_write);
+    }
 
     // Check which required commands are missing (must run AFTER last write)
-    let mut missing: Vec<&str> = Vec::new();
+    let missing: Vec<&str> = required_commands
+        .iter()
+        .filter(|cmd| first_run_from(check, cmd, events, after_write).is_none())
+        .map(String::as_str)
+        .collect();
+
+    if missing.is_empty() {
+        CheckResult::pass(check)
+    } else {
+        commands_missing(check, &missing.join(", "))
+    }
+}
 
+/// For `then.ordered`: each command must run after the previous one, the first
+/// after the last matching edit. Commands chained in one Bash call count in
+/// the order they appear. Reports the first missing or out-of-order command.
+fn check_commands_in_order(
+    check: &Check,
+    required_commands: &[String],
+    events: &[ToolUseEvent],
+    after_write: RunAt,
+) -> CheckResult {
+    let mut from = after_write;
+    let mut previous: Option<&str> = None;
     for cmd in required_commands {
-        let was_run_after_write = events.iter().any(|e| {
+        if let Some((index, offset)) = first_run_from(check, cmd, events, from) {
+            from = (index, offset + 1);
+            previous = Some(cmd);
+            continue;
+        }
+        return match previous {
+            Some(previous) if first_run_from(check, cmd, events, after_write).is_some() => failure(
+                check,
+                format!(
+                    "Check '{}' failed: {} must run after {} (after editing {})",
+                    check.name,
+                    cmd,
+                    previous,
+                    check.when.paths_changed.join(", ")
+                ),
+                cmd,
+            ),
+            _ => commands_missing(check, cmd),
+        };
+    }
+    CheckResult::pass(check)
+}
+
+/// Where a required command ran: the event index, and the byte offset of
+/// the match within that event's Bash command
+type RunAt = (usize, usize);
+
+/// First place at or after `from` where a Bash command satisfies `required`
+fn first_run_from(
+    check: &Check,
+    required: &str,
+    events: &[ToolUseEvent],
+    from: RunAt,
+) -> Option<RunAt> {
+    events
+        .iter()
+        .filter(|e| e.index >= from.0 && e.tool_name == "Bash")
+        .find_map(|e| {
+            let min_offset = if e.index == from.0 { from.1 } else { 0 };
+            command_offset(check, required, e.command.as_deref()?, min_offset)
+                .map(|offset| (e.index, offset))
+        })
+}
+
+fn commands_missing(check: &Check, missing: &str) -> CheckResult {
+    failure(
+        check,
+        format!(
+            "Check '{}' failed: these commands must run after editing {}: {}",
+            check.name,
+            check.when.paths_changed.join(", "),
+            missing
+        ),
+        missing,
+    )
+}
+
+/// Check that each formatter ran after the last matching edit, so nothing
+/// it formatted was touched again. Reports files edited after a formatter.
+fn check_format_commands(
+    check: &Check,
+    patterns: &[Pattern],
+    format_commands: &[String],
+    events: &[ToolUseEvent],
+    config_dir: &Path,
+    repo_root: &Path,
+) -> CheckResult {
+    let matching_edit = |e: &&ToolUseEvent| {
+        e.is_edit()
+            && e.file_path
+                .as_ref()
+                .is_some_and(|p| file_matches_relative(p, patterns, config_dir, repo_root))
+    };
+
+    // If no matching file was edited in this session, skip the check
+    if !events.iter().any(|e| matching_edit(&e)) {
+        return CheckResult::pass(check);
+    }
+
+    let mut not_run: Vec<&str> = Vec::new();
+    let mut edited_after: Vec<(&str, Vec<&str>)> = Vec::new();
+    for cmd in format_commands {
+        let last_run = events.iter().rfind(|e| {
             e.tool_name == "Bash"
-                && e.command.as_ref().is_some_and(|c| c.contains(cmd.as_str()))
-                && e.index > last_write_event_idx
+                && e.command
+                    .as_ref()
+                    .is_some_and(|c| command_satisfies(check, cmd, c))
         });
-        if !was_run_after_write {
-            missing.push(cmd);
+        let Some(last_run) = last_run else {
+            not_run.push(cmd);
+            continue;
+        };
+
+        let mut files: Vec<&str> = events
+            .iter()
+            .filter(|e| e.index > last_run.index)
+            .filter(matching_edit)
+            .filter_map(|e| e.file_path.as_deref())
+            .collect();
+        files.sort_unstable();
+        files.dedup();
+        if !files.is_empty() {
+            edited_after.push((cmd, files));
         }
     }
 
-    if missing.is_empty() {
-        CheckResult {
-            check_name: check.name.clone(),
-            reason: None,
-        }
-    } else {
-        CheckResult {
-            check_name: check.name.clone(),
-            reason: Some(format!(
-                "Check '{}' failed: these commands must run after editing {}: {}",
-                check.name,
-                check.when.paths_changed,
-                missing.join(", ")
-            )),
-        }
+    if not_run.is_empty() && edited_after.is_empty() {
+        return CheckResult::pass(check);
+    }
+
+    let mut problems = Vec::new();
+    if !not_run.is_empty() {
+        problems.push(format!("not run: {}", not_run.join(", ")));
+    }
+    for (cmd, files) in &edited_after {
+        problems.push(format!("{} ran before edits to {}", cmd, files.join(", ")));
     }
+    let missing: Vec<&str> = not_run
+        .iter()
+        .copied()
+        .chain(edited_after.iter().map(|(cmd, _)| *cmd))
+        .collect();
+    failure(
+        check,
+        format!(
+            "Check '{}' failed: these formatters must run last after editing {}: {}",
+            check.name,
+            check.when.paths_changed.join(", "),
+            problems.join("; ")
+        ),
+        &missing.join(", "),
+    )
 }
 
 /// Check that at least one of the specified paths was changed.
@@ -191,64 +660,368 @@ fn check_ensure_changed(
     config_dir: &Path,
     repo_root: &Path,
 ) -> CheckResult {
-    let any_changed = required_paths.iter().any(|required| {
-        let absolute_required = config_dir.join(required);
-        changed_files.iter().any(|f| {
-            let absolute_changed = repo_root.join(f);
-            absolute_changed == absolute_required
+    let changed_required: Vec<&String> = required_paths
+        .iter()
+        .filter(|required| {
+            let absolute_required = config_dir.join(required);
+            changed_files.iter().any(|f| {
+                let absolute_changed = repo_root.join(f);
+                absolute_changed == absolute_required
+            })
         })
-    });
+        .collect();
 
-    if any_changed {
-        CheckResult {
-            check_name: check.name.clone(),
-            reason: None,
+    if !changed_required.is_empty() {
+        if check.then.require_version_increase {
+            if let Some(result) = version_not_increased(check, &changed_required, config_dir) {
+                return result;
+            }
         }
+        CheckResult::pass(check)
     } else {
-        CheckResult {
-            check_name: check.name.clone(),
-            reason: Some(format!(
+        let missing = required_paths.join(", ");
+        failure(
+            check,
+            format!(
                 "Check '{}' failed: one of these files must be changed when editing {}: {}",
                 check.name,
-                check.when.paths_changed,
-                required_paths.join(", ")
-            )),
+                check.when.paths_changed.join(", "),
+                missing
+            ),
+            &missing,
+        )
+    }
+}
+
+/// For require_version_increase: fails unless some changed file's version went up
+/// since HEAD. Files whose old or new version can't be parsed (including new
+/// files) fall back to the plain presence check.
+fn version_not_increased(
+    check: &Check,
+    changed_paths: &[&String],
+    config_dir: &Path,
+) -> Option<CheckResult> {
+    let mut not_increased = Vec::new();
+    for path in changed_paths {
+        let new = fs::read_to_string(config_dir.join(path))
+            .ok()
+            .and_then(|c| parse_version(&c));
+        let old = head_contents(path, config_dir).and_then(|c| parse_version(&c));
+        match (old, new) {
+            (Some(old), Some(new)) if new <= old => {
+                not_increased.push(format!("{} ({} -> {})", path, old, new));
+            }
+            _ => return None,
+        }
+    }
+
+    let detail = not_increased.join(", ");
+    Some(failure(
+        check,
+        format!(
+            "Check '{}' failed: version must increase when editing {}: {}",
+            check.name,
+            check.when.paths_changed.join(", "),
+            detail
+        ),
+        &detail,
+    ))
+}
+
+/// Contents of `path` (relative to `dir`) at HEAD, if it exists there
+fn head_contents(path: &str, dir: &Path) -> Option<String> {
+    let output = Command::new("git")
+        .arg("show")
+        .arg(format!("HEAD:./{}", path))
+        .current_dir(dir)
+        .output()
+        .ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+/// Parses the semver from a `version = "x.y.z"` line, as in version.toml or Cargo.toml
+fn parse_version(contents: &str) -> Option<Version> {
+    contents.lines().find_map(|line| {
+        let (key, value) = line.split_once('=')?;
+        if !key.trim().eq_ignore_ascii_case("version") {
+            return None;
+        }
+        Version::parse(value.trim().trim_matches('"')).ok()
+    })
+}
+
+/// Whether the uncommitted diff of `files` only touches comments, blank lines or indentation.
+/// Anything git can't show a diff for (e.g. untracked files) counts as non-trivial.
+fn diff_is_trivial(files: &[&String], repo_root: &Path) -> bool {
+    let output = Command::new("git")
+        .args(["diff", "HEAD", "--no-color", "--no-ext-diff", "-U0", "--"])
+        .args(files)
+        .current_dir(repo_root)
+        .output();
+
+    match output {
+        Ok(o) if o.status.success() => {
+            let diff = String::from_utf8_lossy(&o.stdout);
+            let diffed = diff
+                .lines()
+                .filter(|l| l.starts_with("diff --git "))
+                .count();
+            diffed == files.len() && is_trivial_diff(&diff)
+        }
+        Ok(o) => {
+            warn!(stderr = %String::from_utf8_lossy(&o.stderr).trim(), "git diff failed");
+            false
+        }
+        Err(e) => {
+            warn!(error = %e, "failed to run git diff");
+            false
+        }
+    }
+}
+
+/// Whether a unified diff only adds/removes blank lines and comments, or
+/// re-indents code. Each hunk must remove and add the same code lines in the
+/// same order, ignoring only leading/trailing whitespace. Comments are only
+/// recognised for languages we know (`//` in `.rs` files); everything else is
+/// treated as code.
+fn is_trivial_diff(diff: &str) -> bool {
+    let mut file = "";
+    let mut in_hunk = false;
+    let mut removed = Vec::new();
+    let mut added = Vec::new();
+
+    for line in diff.lines() {
+        let header = line.strip_prefix("diff --git ");
+        if header.is_some() || line.starts_with("@@") {
+            if removed != added {
+                return false;
+            }
+            removed.clear();
+            added.clear();
+            in_hunk = header.is_none();
+            if let Some(header) = header {
+                file = header.rsplit(' ').next().unwrap_or("");
+            }
+        } else if !in_hunk {
+            continue;
+        } else if let Some(content) = line.strip_prefix('-') {
+            removed.extend(significant_code(file, content));
+        } else if let Some(content) = line.strip_prefix('+') {
+            added.extend(significant_code(file, content));
+        }
+    }
+
+    removed == added
+}
+
+/// A diff line's content without its indentation, or None if it's blank or a comment
+fn significant_code<'a>(file: &str, content: &'a str) -> Option<&'a str> {
+    let trimmed = content.trim();
+    if trimmed.is_empty() || (file.ends_with(".rs") && trimmed.starts_with("//")) {
+        return None;
+    }
+    Some(trimmed)
+}
+
+/// Check that none of the specified paths were changed.
+/// Paths are resolved the same way as for ensure_changed.
+fn check_ensure_no_changes(
+    check: &Check,
+    protected_paths: &[String],
+    changed_files: &[String],
+    config_dir: &Path,
+    repo_root: &Path,
+) -> CheckResult {
+    let touched: Vec<&str> = protected_paths
+        .iter()
+        .filter(|protected| {
+            let absolute_protected = config_dir.join(protected);
+            changed_files
+                .iter()
+                .any(|f| repo_root.join(f) == absolute_protected)
+        })
+        .map(String::as_str)
+        .collect();
+
+    if touched.is_empty() {
+        CheckResult::pass(check)
+    } else {
+        let touched = touched.join(", ");
+        failure(
+            check,
+            format!(
+                "Check '{}' failed: these files must not be changed when editing {}: {}",
+                check.name,
+                check.when.paths_changed.join(", "),
+                touched
+            ),
+            &touched,
+        )
+    }
+}
+
+/// Check that git reports nothing modified or untracked under the given
+/// paths (relative to config dir). Unlike ensure_no_changes this looks at
+/// the working tree, not the session's changed files.
+fn check_require_clean(check: &Check, paths: &[String], config_dir: &Path) -> CheckResult {
+    let output = Command::new("git")
+        .args(["status", "--porcelain", "--untracked-files=all", "--"])
+        .args(paths)
+        .current_dir(config_dir)
+        .output();
+
+    let stdout = match output {
+        Ok(o) if o.status.success() => String::from_utf8_lossy(&o.stdout).to_string(),
+        Ok(o) => {
+            warn!(check = %check.name, stderr = %String::from_utf8_lossy(&o.stderr).trim(), "git status failed");
+            return CheckResult::pass(check);
+        }
+        Err(e) => {
+            warn!(check = %check.name, error = %e, "failed to run git status");
+            return CheckResult::pass(check);
+        }
+    };
+
+    let dirty: Vec<String> = stdout.lines().filter_map(parse_porcelain_line).collect();
+
+    if dirty.is_empty() {
+        CheckResult::pass(check)
+    } else {
+        let dirty = dirty.join(", ");
+        failure(
+            check,
+            format!(
+                "Check '{}' failed: these paths must be committed or removed: {}",
+                check.name, dirty
+            ),
+            &dirty,
+        )
+    }
+}
+
+/// Check that every listed path exists, relative to the config dir
+fn check_ensure_exists(check: &Check, paths: &[String], config_dir: &Path) -> CheckResult {
+    let missing: Vec<&str> = paths
+        .iter()
+        .filter(|path| !config_dir.join(path).exists())
+        .map(String::as_str)
+        .collect();
+
+    if missing.is_empty() {
+        CheckResult::pass(check)
+    } else {
+        let missing = missing.join(", ");
+        failure(
+            check,
+            format!(
+                "Check '{}' failed: these files must exist when editing {}: {}",
+                check.name,
+                check.when.paths_changed.join(", "),
+                missing
+            ),
+            &missing,
+        )
+    }
+}
+
+/// Check that none of the forbidden commands were run this session
+fn check_forbid_commands(
+    check: &Check,
+    forbidden_commands: &[String],
+    events: &[ToolUseEvent],
+) -> CheckResult {
+    let offending = events.iter().find_map(|e| {
+        if e.tool_name != "Bash" {
+            return None;
         }
+        let command = e.command.as_ref()?;
+        forbidden_commands
+            .iter()
+            .any(|forbidden| find_command(check.then.match_mode, forbidden, command).is_some())
+            .then_some(command)
+    });
+
+    match offending {
+        Some(command) => failure(
+            check,
+            format!(
+                "Check '{}' failed: forbidden command was run while editing {}: {}",
+                check.name,
+                check.when.paths_changed.join(", "),
+                command
+            ),
+            command,
+        ),
+        None => CheckResult::pass(check),
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::config::{RufioConfig, Then, When};
+    use crate::config::{find_nearest_config, RufioConfig, Then, When, CONFIG_FILENAME};
+    use std::fs;
     use std::path::PathBuf;
+    use tempfile::TempDir;
 
     fn make_loaded_config(checks: Vec<Check>, config_dir: &Path) -> LoadedConfig {
         LoadedConfig {
-            config: RufioConfig { checks },
+            config: RufioConfig {
+                checks,
+                ..Default::default()
+            },
             config_dir: config_dir.to_path_buf(),
         }
     }
 
-    fn make_check(
-        name: &str,
-        pattern: &str,
-        commands: Option<Vec<&str>>,
-        ensure_changed: Option<Vec<&str>>,
-    ) -> Check {
+    fn on_branch(branch: &str) -> CheckContext<'_> {
+        CheckContext {
+            branch: Some(branch),
+            ..Default::default()
+        }
+    }
+
+    fn make_check(name: &str, pattern: &str, then: Then) -> Check {
         Check {
             name: name.to_string(),
             when: When {
-                paths_changed: pattern.to_string(),
+                paths_changed: vec![pattern.to_string()],
                 path_exists: None,
+                branch: None,
+                skip_branches: None,
+                cwd_prefix: None,
             },
-            then: Then {
-                ensure_commands: commands.map(|c| c.into_iter().map(String::from).collect()),
-                ensure_changed: ensure_changed.map(|c| c.into_iter().map(String::from).collect()),
-            },
+            then,
+            level: Level::Block,
+            depends_on: Vec::new(),
+            on: vec![HookEvent::Stop],
+            inherited_from: None,
+        }
+    }
+
+    /// A transcript event: `input` is the command for Bash, else the file path
+    fn event(tool: &str, input: &str, index: usize) -> ToolUseEvent {
+        let (command, file_path) = match tool {
+            "Bash" => (Some(input.to_string()), None),
+            _ => (None, Some(input.to_string())),
+        };
+        ToolUseEvent {
+            tool_name: tool.to_string(),
+            command,
+            file_path,
+            index,
+            turn: 0,
+            timestamp: None,
         }
     }
 
+    fn strings(items: &[&str]) -> Option<Vec<String>> {
+        Some(items.iter().map(|s| s.to_string()).collect())
+    }
+
     #[test]
     fn test_no_matching_files() {
         let repo_root = PathBuf::from("/repo");
@@ -257,15 +1030,23 @@ mod tests {
             vec![make_check(
                 "test",
                 "**/*.rs",
-                Some(vec!["cargo test"]),
-                None,
+                Then {
+                    ensure_commands: strings(&["cargo test"]),
+                    ..Default::default()
+                },
             )],
             &config_dir,
         );
         let changed_files = vec!["README.md".to_string()];
         let events = vec![];
 
-        let results = run_checks(&loaded, &changed_files, &events, &repo_root);
+        let results = run_checks(
+            &loaded,
+            &changed_files,
+            &events,
+            &repo_root,
+            &CheckContext::default(),
+        );
         assert_eq!(results.len(), 1);
         assert!(results[0].reason.is_none());
     }
@@ -278,54 +1059,92 @@ mod tests {
             vec![make_check(
                 "test",
                 "**/*.rs",
-                Some(vec!["cargo test"]),
-                None,
+                Then {
+                    ensure_commands: strings(&["cargo test"]),
+                    ..Default::default()
+                },
             )],
             &config_dir,
         );
         let changed_files = vec!["src/main.rs".to_string()];
         let events = vec![
-            ToolUseEvent {
-                tool_name: "Write".to_string(),
-                command: None,
-                file_path: Some("/repo/src/main.rs".to_string()),
-                index: 0,
-            },
-            ToolUseEvent {
-                tool_name: "Bash".to_string(),
-                command: Some("cargo test".to_string()),
-                file_path: None,
-                index: 1,
-            },
+            event("Write", "/repo/src/main.rs", 0),
+            event("Bash", "cargo test", 1),
         ];
 
-        let results = run_checks(&loaded, &changed_files, &events, &repo_root);
+        let results = run_checks(
+            &loaded,
+            &changed_files,
+            &events,
+            &repo_root,
+            &CheckContext::default(),
+        );
         assert_eq!(results.len(), 1);
         assert!(results[0].reason.is_none());
     }
 
     #[test]
-    fn test_matching_files_command_not_run() {
+    fn test_multi_edit_counts_as_edit() {
+        let repo_root = PathBuf::from("/repo");
+        let loaded = make_loaded_config(
+            vec![make_check(
+                "test",
+                "**/*.rs",
+                Then {
+                    ensure_commands: strings(&["cargo test"]),
+                    ..Default::default()
+                },
+            )],
+            &repo_root,
+        );
+        let changed_files = vec!["src/main.rs".to_string()];
+        let run = |events: &[ToolUseEvent]| {
+            run_checks(
+                &loaded,
+                &changed_files,
+                events,
+                &repo_root,
+                &CheckContext::default(),
+            )
+        };
+
+        let ran_before = [
+            event("Bash", "cargo test", 0),
+            event("MultiEdit", "/repo/src/main.rs", 1),
+        ];
+        assert!(run(&ran_before)[0].reason.is_some());
+        let ran_after = [
+            event("MultiEdit", "/repo/src/main.rs", 0),
+            event("Bash", "cargo test", 1),
+        ];
+        assert!(run(&ran_after)[0].reason.is_none());
+    }
+
+    #[test]
+    fn test_matching_files_command_not_run() {
         let repo_root = PathBuf::from("/repo");
         let config_dir = repo_root.clone();
         let loaded = make_loaded_config(
             vec![make_check(
                 "test",
                 "**/*.rs",
-                Some(vec!["cargo test"]),
-                None,
+                Then {
+                    ensure_commands: strings(&["cargo test"]),
+                    ..Default::default()
+                },
             )],
             &config_dir,
         );
         let changed_files = vec!["src/main.rs".to_string()];
-        let events = vec![ToolUseEvent {
-            tool_name: "Write".to_string(),
-            command: None,
-            file_path: Some("/repo/src/main.rs".to_string()),
-            index: 0,
-        }];
-
-        let results = run_checks(&loaded, &changed_files, &events, &repo_root);
+        let events = vec![event("Write", "/repo/src/main.rs", 0)];
+
+        let results = run_checks(
+            &loaded,
+            &changed_files,
+            &events,
+            &repo_root,
+            &CheckContext::default(),
+        );
         assert_eq!(results.len(), 1);
         assert!(results[0].reason.is_some());
         assert!(results[0].reason.as_ref().unwrap().contains("cargo test"));
@@ -339,15 +1158,23 @@ mod tests {
             vec![make_check(
                 "version",
                 "**/*.rs",
-                None,
-                Some(vec!["version.toml"]),
+                Then {
+                    ensure_changed: strings(&["version.toml"]),
+                    ..Default::default()
+                },
             )],
             &config_dir,
         );
         let changed_files = vec!["src/main.rs".to_string(), "version.toml".to_string()];
         let events = vec![];
 
-        let results = run_checks(&loaded, &changed_files, &events, &repo_root);
+        let results = run_checks(
+            &loaded,
+            &changed_files,
+            &events,
+            &repo_root,
+            &CheckContext::default(),
+        );
         assert_eq!(results.len(), 1);
         assert!(results[0].reason.is_none());
     }
@@ -360,15 +1187,23 @@ mod tests {
             vec![make_check(
                 "version",
                 "**/*.rs",
-                None,
-                Some(vec!["version.toml"]),
+                Then {
+                    ensure_changed: strings(&["version.toml"]),
+                    ..Default::default()
+                },
             )],
             &config_dir,
         );
         let changed_files = vec!["src/main.rs".to_string()];
         let events = vec![];
 
-        let results = run_checks(&loaded, &changed_files, &events, &repo_root);
+        let results = run_checks(
+            &loaded,
+            &changed_files,
+            &events,
+            &repo_root,
+            &CheckContext::default(),
+        );
         assert_eq!(results.len(), 1);
         assert!(results[0].reason.is_some());
         assert!(results[0].reason.as_ref().unwrap().contains("version.toml"));
@@ -382,28 +1217,26 @@ mod tests {
             vec![make_check(
                 "test",
                 "**/*.rs",
-                Some(vec!["cargo test"]),
-                None,
+                Then {
+                    ensure_commands: strings(&["cargo test"]),
+                    ..Default::default()
+                },
             )],
             &config_dir,
         );
         let changed_files = vec!["src/main.rs".to_string()];
         let events = vec![
-            ToolUseEvent {
-                tool_name: "Bash".to_string(),
-                command: Some("cargo test".to_string()),
-                file_path: None,
-                index: 0,
-            },
-            ToolUseEvent {
-                tool_name: "Write".to_string(),
-                command: None,
-                file_path: Some("/repo/src/main.rs".to_string()),
-                index: 1,
-            },
+            event("Bash", "cargo test", 0),
+            event("Write", "/repo/src/main.rs", 1),
         ];
 
-        let results = run_checks(&loaded, &changed_files, &events, &repo_root);
+        let results = run_checks(
+            &loaded,
+            &changed_files,
+            &events,
+            &repo_root,
+            &CheckContext::default(),
+        );
         assert_eq!(results.len(), 1);
         assert!(results[0].reason.is_some());
     }
@@ -414,29 +1247,39 @@ mod tests {
         let config_dir = repo_root.clone();
         let loaded = make_loaded_config(
             vec![
-                make_check("test", "**/*.rs", Some(vec!["cargo test"]), None),
-                make_check("fmt", "**/*.rs", Some(vec!["cargo fmt"]), None),
+                make_check(
+                    "test",
+                    "**/*.rs",
+                    Then {
+                        ensure_commands: strings(&["cargo test"]),
+                        ..Default::default()
+                    },
+                ),
+                make_check(
+                    "fmt",
+                    "**/*.rs",
+                    Then {
+                        ensure_commands: strings(&["cargo fmt"]),
+                        ..Default::default()
+                    },
+                ),
             ],
             &config_dir,
         );
         let changed_files = vec!["src/main.rs".to_string()];
         let events = vec![
-            ToolUseEvent {
-                tool_name: "Write".to_string(),
-                command: None,
-                file_path: Some("/repo/src/main.rs".to_string()),
-                index: 0,
-            },
-            ToolUseEvent {
-                tool_name: "Bash".to_string(),
-                command: Some("cargo test".to_string()),
-                file_path: None,
-                index: 1,
-            },
+            event("Write", "/repo/src/main.rs", 0),
+            event("Bash", "cargo test", 1),
             // cargo fmt not run
         ];
 
-        let results = run_checks(&loaded, &changed_files, &events, &repo_root);
+        let results = run_checks(
+            &loaded,
+            &changed_files,
+            &events,
+            &repo_root,
+            &CheckContext::default(),
+        );
         assert_eq!(results.len(), 2);
         assert!(results[0].reason.is_none()); // cargo test passed
         assert!(results[1].reason.is_some()); // cargo fmt failed
@@ -451,19 +1294,58 @@ mod tests {
             vec![make_check(
                 "test",
                 "**/*.rs",
-                Some(vec!["cargo test"]),
-                None,
+                Then {
+                    ensure_commands: strings(&["cargo test"]),
+                    ..Default::default()
+                },
             )],
             &config_dir,
         );
         let changed_files = vec!["src/main.rs".to_string()];
         let events = vec![]; // No edits in transcript
 
-        let results = run_checks(&loaded, &changed_files, &events, &repo_root);
+        let results = run_checks(
+            &loaded,
+            &changed_files,
+            &events,
+            &repo_root,
+            &CheckContext::default(),
+        );
         assert_eq!(results.len(), 1);
         assert!(results[0].reason.is_none()); // Should pass - no edit means skip
     }
 
+    #[test]
+    fn test_no_edit_in_transcript_with_command_at_index_zero() {
+        // File changed outside Claude (no Write/Edit event); a command at
+        // index 0 must not be confused with a write at index 0.
+        let repo_root = PathBuf::from("/repo");
+        let config_dir = repo_root.clone();
+        let loaded = make_loaded_config(
+            vec![make_check(
+                "test",
+                "**/*.rs",
+                Then {
+                    ensure_commands: strings(&["cargo test"]),
+                    ..Default::default()
+                },
+            )],
+            &config_dir,
+        );
+        let changed_files = vec!["src/main.rs".to_string()];
+        let events = vec![event("Bash", "cargo test", 0)];
+
+        let results = run_checks(
+            &loaded,
+            &changed_files,
+            &events,
+            &repo_root,
+            &CheckContext::default(),
+        );
+        assert_eq!(results.len(), 1);
+        assert!(results[0].reason.is_none());
+    }
+
     #[test]
     fn test_files_outside_config_dir_ignored() {
         // Config is in /repo/packages/foo, files in /repo/packages/bar should be ignored
@@ -473,8 +1355,10 @@ mod tests {
             vec![make_check(
                 "test",
                 "**/*.rs",
-                Some(vec!["cargo test"]),
-                None,
+                Then {
+                    ensure_commands: strings(&["cargo test"]),
+                    ..Default::default()
+                },
             )],
             &config_dir,
         );
@@ -482,7 +1366,13 @@ mod tests {
         let changed_files = vec!["packages/bar/src/lib.rs".to_string()];
         let events = vec![];
 
-        let results = run_checks(&loaded, &changed_files, &events, &repo_root);
+        let results = run_checks(
+            &loaded,
+            &changed_files,
+            &events,
+            &repo_root,
+            &CheckContext::default(),
+        );
         assert_eq!(results.len(), 1);
         assert!(results[0].reason.is_none()); // No match, check skipped
     }
@@ -496,8 +1386,10 @@ mod tests {
             vec![make_check(
                 "version",
                 "**/*.rs",
-                None,
-                Some(vec!["version.toml"]),
+                Then {
+                    ensure_changed: strings(&["version.toml"]),
+                    ..Default::default()
+                },
             )],
             &config_dir,
         );
@@ -508,11 +1400,67 @@ mod tests {
         ];
         let events = vec![];
 
-        let results = run_checks(&loaded, &changed_files, &events, &repo_root);
+        let results = run_checks(
+            &loaded,
+            &changed_files,
+            &events,
+            &repo_root,
+            &CheckContext::default(),
+        );
         assert_eq!(results.len(), 1);
         assert!(results[0].reason.is_none());
     }
 
+    #[test]
+    fn test_inherited_check_relative_to_ancestor_config() {
+        let temp = TempDir::new().unwrap();
+        let repo_root = temp.path();
+        let pkg_dir = repo_root.join("crates/foo");
+        fs::create_dir_all(&pkg_dir).unwrap();
+        fs::write(
+            repo_root.join(CONFIG_FILENAME),
+            r#"
+checks:
+  - name: version-bump
+    when:
+      paths_changed: "crates/foo/**"
+    then:
+      ensure_changed:
+        - version.toml
+"#,
+        )
+        .unwrap();
+        fs::write(
+            pkg_dir.join(CONFIG_FILENAME),
+            r#"
+inherit: true
+checks:
+  - name: test
+    when:
+      paths_changed: "**/*.ts"
+    then:
+      ensure_commands:
+        - pnpm test
+"#,
+        )
+        .unwrap();
+
+        let loaded = find_nearest_config(&pkg_dir, repo_root).unwrap();
+        assert_eq!(loaded.config_dir, pkg_dir);
+        let run = |changed: &[&str]| {
+            let changed: Vec<String> = changed.iter().map(|f| f.to_string()).collect();
+            run_checks(&loaded, &changed, &[], repo_root, &CheckContext::default())
+        };
+
+        // paths_changed and ensure_changed resolve against the root config
+        let results = run(&["crates/foo/src/lib.rs"]);
+        assert!(results[0].reason.as_ref().unwrap().contains("version.toml"));
+        let results = run(&["crates/foo/src/lib.rs", "version.toml"]);
+        assert!(results[0].reason.is_none());
+        let results = run(&["crates/foo/src/lib.rs", "crates/foo/version.toml"]);
+        assert!(results[0].reason.is_some());
+    }
+
     #[test]
     fn test_project_relative_files_after_prefix_strip() {
         // After filter_to_project strips the monorepo prefix, files are relative
@@ -524,25 +1472,1419 @@ mod tests {
             vec![make_check(
                 "test",
                 "**/*.rs",
-                Some(vec!["cargo test"]),
-                None,
+                Then {
+                    ensure_commands: strings(&["cargo test"]),
+                    ..Default::default()
+                },
             )],
             &config_dir,
         );
         // Files are project-relative (prefix already stripped)
         let changed_files = vec!["src/main.rs".to_string()];
-        let events = vec![ToolUseEvent {
-            tool_name: "Edit".to_string(),
-            command: None,
-            file_path: Some("/repo/projects/foo/src/main.rs".to_string()),
-            index: 0,
-        }];
+        let events = vec![event("Edit", "/repo/projects/foo/src/main.rs", 0)];
 
         // Pass project_root as repo_root (as run_stop_checks now does)
-        let results = run_checks(&loaded, &changed_files, &events, &project_root);
+        let results = run_checks(
+            &loaded,
+            &changed_files,
+            &events,
+            &project_root,
+            &CheckContext::default(),
+        );
         assert_eq!(results.len(), 1);
         // Should block because cargo test wasn't run after the edit
         assert!(results[0].reason.is_some());
         assert!(results[0].reason.as_ref().unwrap().contains("cargo test"));
     }
+
+    #[test]
+    fn test_path_exists_literal() {
+        let temp = TempDir::new().unwrap();
+        let repo_root = temp.path().to_path_buf();
+        fs::write(repo_root.join("package.nix"), "").unwrap();
+        let changed_files = vec!["src/main.rs".to_string()];
+
+        let mut check = make_check(
+            "version",
+            "**/*.rs",
+            Then {
+                ensure_changed: strings(&["version.toml"]),
+                ..Default::default()
+            },
+        );
+        check.when.path_exists = Some("package.nix".to_string());
+        let loaded = make_loaded_config(vec![check], &repo_root);
+        let results = run_checks(
+            &loaded,
+            &changed_files,
+            &[],
+            &repo_root,
+            &CheckContext::default(),
+        );
+        assert!(results[0].reason.is_some());
+
+        let mut check = make_check(
+            "version",
+            "**/*.rs",
+            Then {
+                ensure_changed: strings(&["version.toml"]),
+                ..Default::default()
+            },
+        );
+        check.when.path_exists = Some("missing.nix".to_string());
+        let loaded = make_loaded_config(vec![check], &repo_root);
+        let results = run_checks(
+            &loaded,
+            &changed_files,
+            &[],
+            &repo_root,
+            &CheckContext::default(),
+        );
+        assert!(results[0].reason.is_none());
+    }
+
+    #[test]
+    fn test_path_exists_glob() {
+        let temp = TempDir::new().unwrap();
+        let repo_root = temp.path().to_path_buf();
+        fs::create_dir_all(repo_root.join("src/App")).unwrap();
+        fs::write(repo_root.join("README.md"), "").unwrap();
+        fs::write(repo_root.join("src/App/App.csproj"), "").unwrap();
+        fs::write(repo_root.join("src/App/Program.cs"), "").unwrap();
+        let changed_files = vec!["src/main.rs".to_string()];
+
+        let mut check = make_check(
+            "version",
+            "**/*.rs",
+            Then {
+                ensure_changed: strings(&["version.toml"]),
+                ..Default::default()
+            },
+        );
+        check.when.path_exists = Some("**/*.csproj".to_string());
+        let loaded = make_loaded_config(vec![check], &repo_root);
+        let results = run_checks(
+            &loaded,
+            &changed_files,
+            &[],
+            &repo_root,
+            &CheckContext::default(),
+        );
+        assert!(results[0].reason.is_some());
+
+        let mut check = make_check(
+            "version",
+            "**/*.rs",
+            Then {
+                ensure_changed: strings(&["version.toml"]),
+                ..Default::default()
+            },
+        );
+        check.when.path_exists = Some("**/*.fsproj".to_string());
+        let loaded = make_loaded_config(vec![check], &repo_root);
+        let results = run_checks(
+            &loaded,
+            &changed_files,
+            &[],
+            &repo_root,
+            &CheckContext::default(),
+        );
+        assert!(results[0].reason.is_none());
+    }
+
+    #[test]
+    fn test_forbid_commands_violated() {
+        let repo_root = PathBuf::from("/repo");
+        let loaded = make_loaded_config(
+            vec![make_check(
+                "no-force",
+                "**/*.rs",
+                Then {
+                    forbid_commands: strings(&["git push --force", "rm -rf"]),
+                    ..Default::default()
+                },
+            )],
+            &repo_root,
+        );
+        let changed_files = vec!["src/main.rs".to_string()];
+        let events = vec![
+            event("Bash", "cargo test", 0),
+            event("Bash", "git push --force origin main", 1),
+        ];
+
+        let results = run_checks(
+            &loaded,
+            &changed_files,
+            &events,
+            &repo_root,
+            &CheckContext::default(),
+        );
+        let reason = results[0].reason.as_ref().unwrap();
+        assert!(reason.contains("git push --force origin main"));
+    }
+
+    #[test]
+    fn test_forbid_commands_clean() {
+        let repo_root = PathBuf::from("/repo");
+        let loaded = make_loaded_config(
+            vec![make_check(
+                "no-force",
+                "**/*.rs",
+                Then {
+                    forbid_commands: strings(&["git push --force", "rm -rf"]),
+                    ..Default::default()
+                },
+            )],
+            &repo_root,
+        );
+        let changed_files = vec!["src/main.rs".to_string()];
+        let events = vec![event("Bash", "git push origin main", 0)];
+
+        let results = run_checks(
+            &loaded,
+            &changed_files,
+            &events,
+            &repo_root,
+            &CheckContext::default(),
+        );
+        assert!(results[0].reason.is_none());
+    }
+
+    #[test]
+    fn test_ensure_no_changes_violated() {
+        let repo_root = PathBuf::from("/repo");
+        let loaded = make_loaded_config(
+            vec![make_check(
+                "generated",
+                "**/*.rs",
+                Then {
+                    ensure_no_changes: strings(&["Cargo.lock", "schema.graphql"]),
+                    ..Default::default()
+                },
+            )],
+            &repo_root,
+        );
+        let changed_files = vec!["src/lib.rs".to_string(), "schema.graphql".to_string()];
+
+        let results = run_checks(
+            &loaded,
+            &changed_files,
+            &[],
+            &repo_root,
+            &CheckContext::default(),
+        );
+        let reason = results[0].reason.as_ref().unwrap();
+        assert!(reason.contains("schema.graphql"));
+        assert!(!reason.contains("Cargo.lock"));
+    }
+
+    #[test]
+    fn test_ensure_no_changes_clean() {
+        let repo_root = PathBuf::from("/repo");
+        let loaded = make_loaded_config(
+            vec![make_check(
+                "generated",
+                "**/*.rs",
+                Then {
+                    ensure_no_changes: strings(&["Cargo.lock", "schema.graphql"]),
+                    ..Default::default()
+                },
+            )],
+            &repo_root,
+        );
+        let changed_files = vec!["src/lib.rs".to_string()];
+
+        let results = run_checks(
+            &loaded,
+            &changed_files,
+            &[],
+            &repo_root,
+            &CheckContext::default(),
+        );
+        assert!(results[0].reason.is_none());
+    }
+
+    #[test]
+    fn test_check_result_serializes() {
+        let results = vec![
+            CheckResult {
+                check_name: "tests".to_string(),
+                reason: None,
+                level: Level::Block,
+            },
+            CheckResult {
+                check_name: "version".to_string(),
+                reason: Some("bump it".to_string()),
+                level: Level::Warn,
+            },
+        ];
+
+        let json = serde_json::to_value(&results).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!([
+                {"check": "tests", "passed": true, "reason": null, "level": "block"},
+                {"check": "version", "passed": false, "reason": "bump it", "level": "warn"},
+            ])
+        );
+        assert!(!results[0].blocks());
+        assert!(!results[1].blocks());
+    }
+
+    #[test]
+    fn test_branch_condition() {
+        let repo_root = PathBuf::from("/repo");
+        let mut check = make_check(
+            "version",
+            "**/*.rs",
+            Then {
+                ensure_changed: strings(&["version.toml"]),
+                ..Default::default()
+            },
+        );
+        check.when.branch = Some("feature/*".to_string());
+        let loaded = make_loaded_config(vec![check], &repo_root);
+        let changed_files = vec!["src/main.rs".to_string()];
+
+        let results = run_checks(
+            &loaded,
+            &changed_files,
+            &[],
+            &repo_root,
+            &on_branch("feature/x"),
+        );
+        assert!(results[0].reason.is_some());
+
+        let results = run_checks(&loaded, &changed_files, &[], &repo_root, &on_branch("main"));
+        assert!(results[0].reason.is_none());
+
+        let results = run_checks(
+            &loaded,
+            &changed_files,
+            &[],
+            &repo_root,
+            &CheckContext::default(),
+        );
+        assert!(results[0].reason.is_none());
+    }
+
+    #[test]
+    fn test_skip_branches_condition() {
+        let repo_root = PathBuf::from("/repo");
+        let mut check = make_check(
+            "version",
+            "**/*.rs",
+            Then {
+                ensure_changed: strings(&["version.toml"]),
+                ..Default::default()
+            },
+        );
+        check.when.skip_branches = Some(vec!["main".to_string(), "release/*".to_string()]);
+        let loaded = make_loaded_config(vec![check], &repo_root);
+        let changed_files = vec!["src/main.rs".to_string()];
+
+        let results = run_checks(
+            &loaded,
+            &changed_files,
+            &[],
+            &repo_root,
+            &on_branch("feature/x"),
+        );
+        assert!(results[0].reason.is_some());
+
+        let results = run_checks(&loaded, &changed_files, &[], &repo_root, &on_branch("main"));
+        assert!(results[0].reason.is_none());
+
+        let results = run_checks(
+            &loaded,
+            &changed_files,
+            &[],
+            &repo_root,
+            &on_branch("release/1.0"),
+        );
+        assert!(results[0].reason.is_none());
+
+        let results = run_checks(
+            &loaded,
+            &changed_files,
+            &[],
+            &repo_root,
+            &CheckContext::default(),
+        );
+        assert!(results[0].reason.is_some());
+    }
+
+    #[test]
+    fn test_multiple_paths_changed_patterns() {
+        let repo_root = PathBuf::from("/repo");
+        let mut check = make_check(
+            "build",
+            "**/*.rs",
+            Then {
+                ensure_commands: strings(&["cargo build"]),
+                ..Default::default()
+            },
+        );
+        check.when.paths_changed = vec![
+            "**/*.rs".to_string(),
+            "**/*.toml".to_string(),
+            "build.rs".to_string(),
+        ];
+        let loaded = make_loaded_config(vec![check], &repo_root);
+
+        for file in ["src/lib.rs", "Cargo.toml"] {
+            let changed_files = vec![file.to_string()];
+            let events = vec![event("Edit", &format!("/repo/{}", file), 0)];
+
+            let results = run_checks(
+                &loaded,
+                &changed_files,
+                &events,
+                &repo_root,
+                &CheckContext::default(),
+            );
+            assert!(results[0].reason.is_some(), "{} should trigger", file);
+        }
+
+        let changed_files = vec!["README.md".to_string()];
+        let results = run_checks(
+            &loaded,
+            &changed_files,
+            &[],
+            &repo_root,
+            &CheckContext::default(),
+        );
+        assert!(results[0].reason.is_none());
+    }
+
+    #[test]
+    fn test_glob_bare_pattern_matches_filename() {
+        let pattern = Pattern::new("*.rs").unwrap();
+        assert!(glob_matches(&pattern, Path::new("main.rs")));
+        assert!(glob_matches(
+            &pattern,
+            Path::new("deep/nested/src-ish/foo.rs")
+        ));
+        assert!(!glob_matches(&pattern, Path::new("src/main.rs.orig")));
+    }
+
+    #[test]
+    fn test_glob_bare_literal_is_anchored() {
+        let pattern = Pattern::new("Cargo.toml").unwrap();
+        assert!(glob_matches(&pattern, Path::new("Cargo.toml")));
+        assert!(!glob_matches(&pattern, Path::new("crates/foo/Cargo.toml")));
+
+        let pattern = Pattern::new("build.rs").unwrap();
+        assert!(glob_matches(&pattern, Path::new("build.rs")));
+        assert!(!glob_matches(&pattern, Path::new("vendor/dep/build.rs")));
+    }
+
+    #[test]
+    fn test_glob_anchored_pattern_matches_path() {
+        let pattern = Pattern::new("src/*.rs").unwrap();
+        assert!(glob_matches(&pattern, Path::new("src/main.rs")));
+        assert!(!glob_matches(&pattern, Path::new("src/nested/lib.rs")));
+        assert!(!glob_matches(&pattern, Path::new("deep/nested/src/foo.rs")));
+
+        let pattern = Pattern::new("crates/core/**/*.rs").unwrap();
+        assert!(glob_matches(&pattern, Path::new("crates/core/lib.rs")));
+        assert!(glob_matches(&pattern, Path::new("crates/core/src/a/b.rs")));
+        assert!(!glob_matches(&pattern, Path::new("crates/cli/src/main.rs")));
+
+        let pattern = Pattern::new("**/*.rs").unwrap();
+        assert!(glob_matches(&pattern, Path::new("main.rs")));
+        assert!(glob_matches(&pattern, Path::new("src/a/b.rs")));
+    }
+
+    #[test]
+    fn test_custom_message() {
+        let repo_root = PathBuf::from("/repo");
+        let check = make_check(
+            "ci",
+            "**/*.rs",
+            Then {
+                ensure_commands: strings(&["just ci"]),
+                message: Some("Run `just ci` before finishing - see CONTRIBUTING.md".to_string()),
+                ..Default::default()
+            },
+        );
+        let loaded = make_loaded_config(vec![check], &repo_root);
+        let changed_files = vec!["src/main.rs".to_string()];
+        let events = vec![event("Edit", "/repo/src/main.rs", 0)];
+
+        let results = run_checks(
+            &loaded,
+            &changed_files,
+            &events,
+            &repo_root,
+            &CheckContext::default(),
+        );
+        assert_eq!(
+            results[0].reason.as_deref(),
+            Some("Run `just ci` before finishing - see CONTRIBUTING.md")
+        );
+    }
+
+    #[test]
+    fn test_custom_message_missing_placeholder() {
+        let repo_root = PathBuf::from("/repo");
+        let check = make_check(
+            "checks",
+            "**/*.rs",
+            Then {
+                ensure_commands: strings(&["cargo test", "cargo clippy", "cargo fmt"]),
+                message: Some("Still need to run: {missing}".to_string()),
+                ..Default::default()
+            },
+        );
+        let loaded = make_loaded_config(vec![check], &repo_root);
+        let changed_files = vec!["src/main.rs".to_string()];
+        let events = vec![
+            event("Edit", "/repo/src/main.rs", 0),
+            event("Bash", "cargo clippy", 1),
+        ];
+
+        let results = run_checks(
+            &loaded,
+            &changed_files,
+            &events,
+            &repo_root,
+            &CheckContext::default(),
+        );
+        assert_eq!(
+            results[0].reason.as_deref(),
+            Some("Still need to run: cargo test, cargo fmt")
+        );
+    }
+
+    #[test]
+    fn test_command_alias_satisfies_requirement() {
+        let repo_root = PathBuf::from("/repo");
+        let check = make_check(
+            "test",
+            "**/*.rs",
+            Then {
+                ensure_commands: strings(&["cargo test"]),
+                command_aliases: Some(
+                    [(
+                        "cargo test".to_string(),
+                        vec!["just test".to_string(), "make test".to_string()],
+                    )]
+                    .into_iter()
+                    .collect(),
+                ),
+                ..Default::default()
+            },
+        );
+        let loaded = make_loaded_config(vec![check], &repo_root);
+        let changed_files = vec!["src/main.rs".to_string()];
+        let mut events = vec![
+            event("Edit", "/repo/src/main.rs", 0),
+            event("Bash", "just lint", 1),
+        ];
+
+        let results = run_checks(
+            &loaded,
+            &changed_files,
+            &events,
+            &repo_root,
+            &CheckContext::default(),
+        );
+        assert!(results[0].reason.is_some());
+
+        events.push(event("Bash", "just test", 2));
+        let results = run_checks(
+            &loaded,
+            &changed_files,
+            &events,
+            &repo_root,
+            &CheckContext::default(),
+        );
+        assert!(results[0].reason.is_none());
+    }
+
+    fn two_turn_events() -> Vec<ToolUseEvent> {
+        vec![
+            ToolUseEvent {
+                turn: 1,
+                ..event("Edit", "/repo/src/main.rs", 0)
+            },
+            ToolUseEvent {
+                turn: 1,
+                ..event("Bash", "git push --force", 1)
+            },
+            ToolUseEvent {
+                turn: 2,
+                ..event("Edit", "/repo/README.md", 2)
+            },
+        ]
+    }
+
+    #[test]
+    fn test_current_turn_only_ignores_earlier_turns() {
+        let repo_root = PathBuf::from("/repo");
+        let changed_files = vec!["src/main.rs".to_string()];
+        let events = two_turn_events();
+        let context = CheckContext {
+            current_turn: 2,
+            ..Default::default()
+        };
+
+        let mut forbid = make_check(
+            "no-force",
+            "**/*.rs",
+            Then {
+                forbid_commands: strings(&["git push --force"]),
+                ..Default::default()
+            },
+        );
+        let mut tests = make_check(
+            "test",
+            "**/*.rs",
+            Then {
+                ensure_commands: strings(&["cargo test"]),
+                ..Default::default()
+            },
+        );
+
+        // Whole session: the turn-1 edit and force push both count
+        let loaded = make_loaded_config(vec![forbid.clone(), tests.clone()], &repo_root);
+        let results = run_checks(&loaded, &changed_files, &events, &repo_root, &context);
+        assert!(results[0].reason.is_some());
+        assert!(results[1].reason.is_some());
+
+        // Scoped to turn 2: no .rs edit and no force push this turn
+        forbid.then.current_turn_only = true;
+        tests.then.current_turn_only = true;
+        let loaded = make_loaded_config(vec![forbid, tests], &repo_root);
+        let results = run_checks(&loaded, &changed_files, &events, &repo_root, &context);
+        assert!(results[0].reason.is_none());
+        assert!(results[1].reason.is_none());
+    }
+
+    #[test]
+    fn test_current_turn_only_requires_command_this_turn() {
+        let repo_root = PathBuf::from("/repo");
+        let changed_files = vec!["src/main.rs".to_string()];
+        let mut events = two_turn_events();
+        events.push(ToolUseEvent {
+            turn: 2,
+            ..event("Edit", "/repo/src/lib.rs", 3)
+        });
+        let context = CheckContext {
+            current_turn: 2,
+            ..Default::default()
+        };
+
+        let tests = make_check(
+            "test",
+            "**/*.rs",
+            Then {
+                ensure_commands: strings(&["cargo test"]),
+                current_turn_only: true,
+                ..Default::default()
+            },
+        );
+        let loaded = make_loaded_config(vec![tests], &repo_root);
+        let results = run_checks(&loaded, &changed_files, &events, &repo_root, &context);
+        assert!(results[0].reason.as_ref().unwrap().contains("cargo test"));
+    }
+
+    #[test]
+    fn test_transcript_and_git_paths_match_consistently() {
+        let repo_root = PathBuf::from("/repo");
+        let changed_files = vec!["src/main.rs".to_string()];
+        let check = make_check(
+            "test",
+            "src/*.rs",
+            Then {
+                ensure_commands: strings(&["cargo test"]),
+                ..Default::default()
+            },
+        );
+        let loaded = make_loaded_config(vec![check], &repo_root);
+
+        for path in [
+            "/repo/src/main.rs",
+            "/repo/./src/../src/main.rs",
+            "src/main.rs",
+        ] {
+            let mut events = vec![event("Edit", path, 0)];
+            crate::transcript::relativize_paths(&mut events, &repo_root);
+            assert_eq!(events[0].file_path.as_deref(), Some("src/main.rs"));
+
+            let results = run_checks(
+                &loaded,
+                &changed_files,
+                &events,
+                &repo_root,
+                &CheckContext::default(),
+            );
+            assert!(
+                results[0].reason.as_ref().unwrap().contains("cargo test"),
+                "edit via {path} should require cargo test"
+            );
+        }
+    }
+
+    #[test]
+    fn test_explain_file() {
+        let temp = TempDir::new().unwrap();
+        let repo_root = temp.path();
+        fs::write(repo_root.join("package.nix"), "").unwrap();
+
+        let tests = make_check(
+            "test",
+            "**/*.rs",
+            Then {
+                ensure_commands: strings(&["cargo test"]),
+                ..Default::default()
+            },
+        );
+        assert_eq!(
+            explain_file(&tests, "src/main.rs", repo_root, repo_root),
+            Trigger::Triggers
+        );
+        assert_eq!(
+            explain_file(&tests, "README.md", repo_root, repo_root),
+            Trigger::NoPathMatch
+        );
+        assert_eq!(
+            describe_requirement(&tests),
+            "run after editing: cargo test"
+        );
+
+        let mut nix = make_check(
+            "version",
+            "**/*.rs",
+            Then {
+                ensure_changed: strings(&["version.toml"]),
+                ..Default::default()
+            },
+        );
+        nix.when.path_exists = Some("flake.nix".to_string());
+        assert_eq!(
+            explain_file(&nix, "src/main.rs", repo_root, repo_root),
+            Trigger::PathMissing
+        );
+
+        let bad = make_check(
+            "bad",
+            "[",
+            Then {
+                ensure_commands: strings(&["cargo test"]),
+                ..Default::default()
+            },
+        );
+        assert_eq!(
+            explain_file(&bad, "src/main.rs", repo_root, repo_root),
+            Trigger::InvalidGlob("[".to_string())
+        );
+    }
+
+    fn git(dir: &Path, args: &[&str]) {
+        let status = std::process::Command::new("git")
+            .args(args)
+            .current_dir(dir)
+            .status()
+            .unwrap();
+        assert!(status.success(), "git {args:?} failed");
+    }
+
+    #[test]
+    fn test_require_clean() {
+        let temp = TempDir::new().unwrap();
+        let repo_root = temp.path();
+        git(repo_root, &["init", "--quiet"]);
+        fs::create_dir_all(repo_root.join("dist")).unwrap();
+        fs::write(repo_root.join("dist/app.js"), "v1").unwrap();
+        git(repo_root, &["add", "."]);
+        git(
+            repo_root,
+            &[
+                "-c",
+                "user.name=test",
+                "-c",
+                "user.email=test@example.com",
+                "commit",
+                "--quiet",
+                "-m",
+                "init",
+            ],
+        );
+
+        let check = make_check(
+            "release",
+            "src/**",
+            Then {
+                require_clean: strings(&["dist"]),
+                ..Default::default()
+            },
+        );
+        let loaded = make_loaded_config(vec![check], repo_root);
+        let changed_files = vec!["src/lib.rs".to_string()];
+        let run = || {
+            run_checks(
+                &loaded,
+                &changed_files,
+                &[],
+                repo_root,
+                &CheckContext::default(),
+            )
+        };
+
+        // Committed and untouched
+        assert!(run()[0].reason.is_none());
+
+        // Dirty outside the required paths doesn't count
+        fs::write(repo_root.join("notes.txt"), "scratch").unwrap();
+        assert!(run()[0].reason.is_none());
+
+        fs::write(repo_root.join("dist/app.js"), "v2").unwrap();
+        fs::write(repo_root.join("dist/new.js"), "new").unwrap();
+        let reason = run()[0].reason.clone().unwrap();
+        assert!(reason.contains("dist/app.js"), "reason: {reason}");
+        assert!(reason.contains("dist/new.js"), "reason: {reason}");
+
+        // Renames report the new path; quoted paths are unquoted
+        fs::write(repo_root.join("dist/app.js"), "v1").unwrap();
+        fs::remove_file(repo_root.join("dist/new.js")).unwrap();
+        git(repo_root, &["mv", "dist/app.js", "dist/main.js"]);
+        fs::write(repo_root.join("dist/café.js"), "new").unwrap();
+        let reason = run()[0].reason.clone().unwrap();
+        assert!(
+            reason.ends_with("dist/café.js, dist/main.js")
+                || reason.ends_with("dist/main.js, dist/café.js"),
+            "reason: {reason}"
+        );
+    }
+
+    #[test]
+    fn test_ensure_exists() {
+        let temp = TempDir::new().unwrap();
+        let config_dir = temp.path();
+        let check = make_check(
+            "changelog",
+            "**/*.rs",
+            Then {
+                ensure_exists: strings(&["CHANGELOG.md", "LICENSE"]),
+                ..Default::default()
+            },
+        );
+        let loaded = make_loaded_config(vec![check], config_dir);
+        let run = |changed: &str| {
+            run_checks(
+                &loaded,
+                &[changed.to_string()],
+                &[],
+                config_dir,
+                &CheckContext::default(),
+            )
+            .remove(0)
+            .reason
+        };
+
+        fs::write(config_dir.join("LICENSE"), "MIT").unwrap();
+        let reason = run("src/lib.rs").unwrap();
+        assert!(reason.ends_with(": CHANGELOG.md"), "reason: {reason}");
+
+        fs::write(config_dir.join("CHANGELOG.md"), "").unwrap();
+        assert!(run("src/lib.rs").is_none());
+
+        // Only when the check triggers
+        fs::remove_file(config_dir.join("LICENSE")).unwrap();
+        assert!(run("README.md").is_none());
+        assert!(run("src/lib.rs").is_some());
+    }
+
+    #[test]
+    fn test_is_trivial_diff() {
+        let comment_only = "\
+diff --git a/src/lib.rs b/src/lib.rs
+index 1111111..2222222 100644
+--- a/src/lib.rs
++++ b/src/lib.rs
+@@ -1,0 +2,2 @@
++/// Adds two numbers
++
+@@ -10 +11 @@
+-    // old note
++    // new note
+";
+        assert!(is_trivial_diff(comment_only));
+
+        let reindent = "\
+diff --git a/src/lib.rs b/src/lib.rs
+@@ -3 +3 @@
+-  let x = 1;
++    let x = 1;
+";
+        assert!(is_trivial_diff(reindent));
+
+        // Whitespace inside a line is significant, e.g. in a string literal
+        let string_literal = "\
+diff --git a/src/lib.rs b/src/lib.rs
+@@ -3 +3 @@
+-    greet(\"hello world\");
++    greet(\"helloworld\");
+";
+        assert!(!is_trivial_diff(string_literal));
+
+        // Reordered lines change behaviour
+        let reordered = "\
+diff --git a/src/lib.rs b/src/lib.rs
+@@ -3,2 +3,2 @@
+-    first();
+-    second();
++    second();
++    first();
+";
+        assert!(!is_trivial_diff(reordered));
+
+        // So does moving a line elsewhere in the same file
+        let moved_within_file = "\
+diff --git a/src/lib.rs b/src/lib.rs
+@@ -3 +2,0 @@
+-    setup();
+@@ -20,0 +20 @@
++    setup();
+";
+        assert!(!is_trivial_diff(moved_within_file));
+
+        let code_change = "\
+diff --git a/src/lib.rs b/src/lib.rs
+@@ -3 +3 @@
+-    let x = 1; // one
++    let x = 2; // one
+";
+        assert!(!is_trivial_diff(code_change));
+
+        // Lines removed from one file and added to another aren't a no-op
+        let moved = "\
+diff --git a/src/a.rs b/src/a.rs
+@@ -3 +2,0 @@
+-fn helper() {}
+diff --git a/src/b.rs b/src/b.rs
+@@ -1,0 +2 @@
++fn helper() {}
+";
+        assert!(!is_trivial_diff(moved));
+
+        // `//` is only a comment in languages we know about
+        let python = "\
+diff --git a/tool.py b/tool.py
+@@ -1,0 +2 @@
++// 2
+";
+        assert!(!is_trivial_diff(python));
+    }
+
+    #[test]
+    fn test_skip_trivial_changes() {
+        let temp = TempDir::new().unwrap();
+        let repo_root = temp.path();
+        git(repo_root, &["init", "--quiet"]);
+        fs::create_dir_all(repo_root.join("src")).unwrap();
+        fs::write(repo_root.join("src/lib.rs"), "fn main() {}\n").unwrap();
+        git(repo_root, &["add", "."]);
+        git(
+            repo_root,
+            &[
+                "-c",
+                "user.name=test",
+                "-c",
+                "user.email=test@example.com",
+                "commit",
+                "--quiet",
+                "-m",
+                "init",
+            ],
+        );
+
+        let check = make_check(
+            "version-bump",
+            "**/*.rs",
+            Then {
+                ensure_changed: strings(&["version.toml"]),
+                skip_trivial_changes: true,
+                ..Default::default()
+            },
+        );
+        let loaded = make_loaded_config(vec![check], repo_root);
+        let changed_files = vec!["src/lib.rs".to_string()];
+        let run = || {
+            run_checks(
+                &loaded,
+                &changed_files,
+                &[],
+                repo_root,
+                &CheckContext::default(),
+            )
+        };
+
+        fs::write(
+            repo_root.join("src/lib.rs"),
+            "// Entry point\nfn main() {}\n",
+        )
+        .unwrap();
+        assert!(run()[0].reason.is_none());
+
+        fs::write(repo_root.join("src/lib.rs"), "fn main() { run() }\n").unwrap();
+        assert!(run()[0].reason.is_some());
+
+        // Untracked files have no diff to inspect, so they always need a bump
+        git(repo_root, &["checkout", "--quiet", "--", "."]);
+        fs::write(repo_root.join("src/new.rs"), "// nothing yet\n").unwrap();
+        let changed_files = vec!["src/new.rs".to_string()];
+        let results = run_checks(
+            &loaded,
+            &changed_files,
+            &[],
+            repo_root,
+            &CheckContext::default(),
+        );
+        assert!(results[0].reason.is_some());
+    }
+
+    #[test]
+    fn test_require_version_increase() {
+        let temp = TempDir::new().unwrap();
+        let repo_root = temp.path();
+        git(repo_root, &["init", "--quiet"]);
+        fs::write(repo_root.join("version.toml"), "version = \"1.2.0\"\n").unwrap();
+        git(repo_root, &["add", "."]);
+        git(
+            repo_root,
+            &[
+                "-c",
+                "user.name=test",
+                "-c",
+                "user.email=test@example.com",
+                "commit",
+                "--quiet",
+                "-m",
+                "init",
+            ],
+        );
+
+        let check = make_check(
+            "version-bump",
+            "**/*.rs",
+            Then {
+                ensure_changed: strings(&["version.toml"]),
+                require_version_increase: true,
+                ..Default::default()
+            },
+        );
+        let loaded = make_loaded_config(vec![check], repo_root);
+        let changed_files = vec!["src/lib.rs".to_string(), "version.toml".to_string()];
+        let run = |version: &str| {
+            fs::write(repo_root.join("version.toml"), version).unwrap();
+            run_checks(
+                &loaded,
+                &changed_files,
+                &[],
+                repo_root,
+                &CheckContext::default(),
+            )
+            .remove(0)
+            .reason
+        };
+
+        // Reformatted but equal
+        let reason = run("version   =   \"1.2.0\"\n").unwrap();
+        assert!(
+            reason.contains("version.toml (1.2.0 -> 1.2.0)"),
+            "reason: {reason}"
+        );
+
+        let reason = run("version = \"1.1.9\"\n").unwrap();
+        assert!(
+            reason.contains("version.toml (1.2.0 -> 1.1.9)"),
+            "reason: {reason}"
+        );
+
+        assert!(run("version = \"1.2.1\"\n").is_none());
+
+        // Unparseable versions fall back to the presence check
+        assert!(run("version = \"next\"\n").is_none());
+    }
+
+    #[test]
+    fn test_parse_version() {
+        assert_eq!(
+            parse_version("Version = \"0.18.0\"\n"),
+            Some(Version::new(0, 18, 0))
+        );
+        assert_eq!(
+            parse_version("[package]\nname = \"x\"\nversion = \"2.0.0-rc.1\"\n"),
+            Some(Version::parse("2.0.0-rc.1").unwrap())
+        );
+        assert_eq!(parse_version("name = \"x\"\n"), None);
+    }
+
+    #[test]
+    fn test_depends_on() {
+        let repo_root = PathBuf::from("/repo");
+        let changed_files = vec!["src/main.rs".to_string()];
+        let edit = event("Edit", "/repo/src/main.rs", 0);
+        let test_run = event("Bash", "cargo test", 1);
+
+        // Dependent listed first: it must still wait for its dependency
+        let mut clippy = make_check(
+            "clippy",
+            "**/*.rs",
+            Then {
+                ensure_commands: strings(&["cargo clippy"]),
+                ..Default::default()
+            },
+        );
+        clippy.depends_on = vec!["test".to_string()];
+        let test = make_check(
+            "test",
+            "**/*.rs",
+            Then {
+                ensure_commands: strings(&["cargo test"]),
+                ..Default::default()
+            },
+        );
+        let loaded = make_loaded_config(vec![clippy, test], &repo_root);
+
+        // Dependency passes: the dependent runs and reports
+        let events = vec![edit.clone(), test_run];
+        let results = run_checks(
+            &loaded,
+            &changed_files,
+            &events,
+            &repo_root,
+            &CheckContext::default(),
+        );
+        let names: Vec<&str> = results.iter().map(|r| r.check_name.as_str()).collect();
+        assert_eq!(names, vec!["clippy", "test"]);
+        assert!(results[0].reason.as_ref().unwrap().contains("cargo clippy"));
+        assert!(results[1].reason.is_none());
+
+        // Dependency fails: the dependent is suppressed
+        let results = run_checks(
+            &loaded,
+            &changed_files,
+            &[edit],
+            &repo_root,
+            &CheckContext::default(),
+        );
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].check_name, "test");
+        assert!(results[0].reason.is_some());
+    }
+
+    #[test]
+    fn test_format_commands() {
+        let repo_root = PathBuf::from("/repo");
+        let changed_files = vec!["src/main.rs".to_string()];
+        let check = make_check(
+            "fmt",
+            "**/*.rs",
+            Then {
+                format_commands: strings(&["cargo fmt"]),
+                ..Default::default()
+            },
+        );
+        let loaded = make_loaded_config(vec![check], &repo_root);
+        let run = |events: &[ToolUseEvent]| {
+            run_checks(
+                &loaded,
+                &changed_files,
+                events,
+                &repo_root,
+                &CheckContext::default(),
+            )
+            .remove(0)
+            .reason
+        };
+
+        // Format last: pass, even with unrelated edits afterwards
+        let events = [
+            event("Edit", "/repo/src/main.rs", 0),
+            event("Bash", "cargo fmt", 1),
+            event("Edit", "/repo/README.md", 2),
+        ];
+        assert_eq!(run(&events), None);
+
+        // Edited after formatting: fail, naming the file
+        let events = [
+            event("Edit", "/repo/src/main.rs", 0),
+            event("Bash", "cargo fmt", 1),
+            event("Edit", "/repo/src/lib.rs", 2),
+        ];
+        let reason = run(&events).unwrap();
+        assert!(
+            reason.contains("cargo fmt ran before edits to /repo/src/lib.rs"),
+            "{reason}"
+        );
+
+        // Never run: fail
+        let reason = run(&[event("Edit", "/repo/src/main.rs", 0)]).unwrap();
+        assert!(reason.contains("not run: cargo fmt"), "{reason}");
+
+        // No matching edits: nothing to format
+        assert_eq!(run(&[event("Edit", "/repo/README.md", 0)]), None);
+    }
+
+    #[test]
+    fn test_is_backgrounded() {
+        assert!(is_backgrounded("cargo test &"));
+        assert!(is_backgrounded("cargo test&  "));
+        assert!(is_backgrounded("cargo test & disown"));
+        assert!(is_backgrounded("cargo test | disown"));
+        assert!(is_backgrounded("cargo test &\ndisown -h"));
+
+        assert!(!is_backgrounded("cargo test"));
+        assert!(!is_backgrounded("cargo build &&"));
+        assert!(!is_backgrounded("echo a\\&"));
+        assert!(!is_backgrounded("cargo test 2>&1"));
+        assert!(!is_backgrounded("cargo test & wait"));
+        assert!(!is_backgrounded("echo disowned"));
+    }
+
+    #[test]
+    fn test_ignore_background() {
+        let repo_root = PathBuf::from("/repo");
+        let changed_files = vec!["src/main.rs".to_string()];
+        let check = make_check(
+            "test",
+            "**/*.rs",
+            Then {
+                ensure_commands: strings(&["cargo test"]),
+                ignore_background: true,
+                ..Default::default()
+            },
+        );
+        let loaded = make_loaded_config(vec![check], &repo_root);
+        let run = |command: &str| {
+            let events = [
+                event("Edit", "/repo/src/main.rs", 0),
+                event("Bash", command, 1),
+            ];
+            run_checks(
+                &loaded,
+                &changed_files,
+                &events,
+                &repo_root,
+                &CheckContext::default(),
+            )
+            .remove(0)
+            .reason
+        };
+
+        assert!(run("cargo test &").is_some());
+        assert!(run("cargo test").is_none());
+    }
+
+    #[test]
+    fn test_ordered_commands() {
+        let repo_root = PathBuf::from("/repo");
+        let changed_files = vec!["src/main.rs".to_string()];
+        let check = make_check(
+            "release",
+            "**/*.rs",
+            Then {
+                ensure_commands: strings(&["cargo build", "cargo test", "cargo publish --dry-run"]),
+                ordered: true,
+                ..Default::default()
+            },
+        );
+        let loaded = make_loaded_config(vec![check], &repo_root);
+        let run = |commands: &[&str]| {
+            let mut events = vec![
+                event("Bash", "cargo publish --dry-run", 0),
+                event("Edit", "/repo/src/main.rs", 1),
+            ];
+            events.extend(
+                commands
+                    .iter()
+                    .enumerate()
+                    .map(|(i, command)| event("Bash", command, i + 2)),
+            );
+            run_checks(
+                &loaded,
+                &changed_files,
+                &events,
+                &repo_root,
+                &CheckContext::default(),
+            )
+            .remove(0)
+            .reason
+        };
+
+        assert!(run(&["cargo build", "cargo test", "cargo publish --dry-run"]).is_none());
+        // Extra runs in between don't matter
+        assert!(run(&[
+            "cargo test",
+            "cargo build",
+            "cargo test",
+            "cargo publish --dry-run"
+        ])
+        .is_none());
+
+        let reason = run(&["cargo publish --dry-run", "cargo test", "cargo build"]).unwrap();
+        assert!(
+            reason.contains("cargo test must run after cargo build"),
+            "reason: {reason}"
+        );
+
+        let reason = run(&["cargo build", "cargo publish --dry-run"]).unwrap();
+        assert!(
+            reason.ends_with("these commands must run after editing **/*.rs: cargo test"),
+            "reason: {reason}"
+        );
+    }
+
+    #[test]
+    fn test_ordered_commands_chained() {
+        let repo_root = PathBuf::from("/repo");
+        let changed_files = vec!["src/main.rs".to_string()];
+        let check = make_check(
+            "release",
+            "**/*.rs",
+            Then {
+                ensure_commands: strings(&["cargo test", "cargo publish --dry-run"]),
+                ordered: true,
+                ..Default::default()
+            },
+        );
+        let loaded = make_loaded_config(vec![check], &repo_root);
+        let run = |command: &str| {
+            let events = [
+                event("Edit", "/repo/src/main.rs", 0),
+                event("Bash", command, 1),
+            ];
+            run_checks(
+                &loaded,
+                &changed_files,
+                &events,
+                &repo_root,
+                &CheckContext::default(),
+            )
+            .remove(0)
+            .reason
+        };
+
+        assert!(run("cargo test && cargo publish --dry-run").is_none());
+        // A later run in the same call still counts
+        assert!(run("cargo publish --dry-run; cargo test && cargo publish --dry-run").is_none());
+
+        let reason = run("cargo publish --dry-run && cargo test").unwrap();
+        assert!(
+            reason.contains("cargo publish --dry-run must run after cargo test"),
+            "reason: {reason}"
+        );
+    }
+
+    #[test]
+    fn test_match_tokens() {
+        let repo_root = PathBuf::from("/repo");
+        let changed_files = vec!["src/main.rs".to_string()];
+        let check = make_check(
+            "lint",
+            "**/*.rs",
+            Then {
+                ensure_commands: strings(&["cargo clippy --all-targets --all-features"]),
+                match_mode: MatchMode::Tokens,
+                ..Default::default()
+            },
+        );
+        let loaded = make_loaded_config(vec![check], &repo_root);
+        let run = |command: &str| {
+            let events = [
+                event("Edit", "/repo/src/main.rs", 0),
+                event("Bash", command, 1),
+            ];
+            run_checks(
+                &loaded,
+                &changed_files,
+                &events,
+                &repo_root,
+                &CheckContext::default(),
+            )
+            .remove(0)
+            .reason
+        };
+
+        assert!(run("cargo clippy --all-targets --all-features").is_none());
+        assert!(run("cargo clippy --all-features --all-targets -- -D warnings").is_none());
+        assert!(run("cargo fmt && cargo clippy --all-features --all-targets").is_none());
+        assert!(run("cargo clippy --all-features").is_some());
+        // Every word has to be in the same step
+        assert!(run("cargo clippy --all-targets; echo --all-features").is_some());
+    }
+
+    #[test]
+    fn test_find_command() {
+        let command = "cd crates/core && cargo test --release --lib";
+        assert_eq!(
+            find_command(MatchMode::Substring, "cargo test --lib", command),
+            None
+        );
+        assert_eq!(
+            find_command(MatchMode::Tokens, "cargo test --lib", command),
+            Some(17)
+        );
+        assert!(runs_in_dir(&command[..17], "crates/core"));
+        assert_eq!(
+            find_command(MatchMode::Tokens, "cargo test --doc", command),
+            None
+        );
+    }
+
+    #[test]
+    fn test_runs_in_dir() {
+        assert!(runs_in_dir("cd packages/foo && ", "packages/foo"));
+        assert!(runs_in_dir("cd ./packages/foo/ && ", "packages/foo"));
+        assert!(runs_in_dir(
+            "cd /home/me/repo/packages/foo; ",
+            "packages/foo"
+        ));
+        assert!(runs_in_dir("cd \"packages/foo\" && ", "packages/foo/"));
+        assert!(runs_in_dir("", "."));
+
+        assert!(!runs_in_dir("", "packages/foo"));
+        assert!(!runs_in_dir("cd packages/bar && ", "packages/foo"));
+        assert!(!runs_in_dir("cd packages/foo && cd .. && ", "packages/foo"));
+        assert!(!runs_in_dir("cd /repo/packages/foobar && ", "packages/foo"));
+    }
+
+    #[test]
+    fn test_cwd_prefix_matching() {
+        let repo_root = PathBuf::from("/repo");
+        let changed_files = vec!["packages/foo/src/index.ts".to_string()];
+        let mut check = make_check(
+            "foo-tests",
+            "**/*.ts",
+            Then {
+                ensure_commands: strings(&["pnpm test"]),
+                ..Default::default()
+            },
+        );
+        check.when.cwd_prefix = Some("packages/foo".to_string());
+        let loaded = make_loaded_config(vec![check], &repo_root);
+        let run = |command: &str| {
+            let events = [
+                event("Edit", "/repo/packages/foo/src/index.ts", 0),
+                event("Bash", command, 1),
+            ];
+            run_checks(
+                &loaded,
+                &changed_files,
+                &events,
+                &repo_root,
+                &CheckContext::default(),
+            )
+            .remove(0)
+            .reason
+        };
+
+        assert!(run("cd packages/foo && pnpm test").is_none());
+        assert!(run("pnpm test").is_some());
+        assert!(run("cd packages/bar && pnpm test").is_some());
+    }
 }
@@ -0,0 +1,187 @@
+use anyhow::{bail, Result};
+use std::path::PathBuf;
+
+/// How the check subcommand reports results
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    Text,
+    Json,
+}
+
+/// What rufio was asked to do on the command line
+#[derive(Debug, PartialEq, Eq)]
+pub enum Command {
+    /// Default: handle a hook event read from stdin
+    Hook,
+    /// Evaluate Stop checks for a hook input on stdin and report every result
+    Check {
+        format: OutputFormat,
+        /// Explicit config file, bypassing discovery
+        config: Option<PathBuf>,
+    },
+    /// Write a starter rufio-hooks.yaml into the current directory
+    Init,
+    /// Show which checks a change to this file would trigger
+    Explain { path: PathBuf },
+    /// Print the rufio version and build commit
+    Version,
+}
+
+/// Parse command-line arguments (excluding the program name)
+pub fn parse_args(args: &[String]) -> Result<Command> {
+    let Some((subcommand, rest)) = args.split_first() else {
+        return Ok(Command::Hook);
+    };
+
+    match subcommand.as_str() {
+        "check" => {
+            let mut format = OutputFormat::Text;
+            let mut config = None;
+            let mut iter = rest.iter();
+            while let Some(arg) = iter.next() {
+                if let Some(value) = flag_value(arg, "--format", &mut iter)? {
+                    format = parse_format(value)?;
+                } else if let Some(value) = flag_value(arg, "--config", &mut iter)? {
+                    config = Some(PathBuf::from(value));
+                } else {
+                    bail!("unknown argument for check: {}", arg);
+                }
+            }
+            Ok(Command::Check { format, config })
+        }
+        "init" => {
+            if let Some(arg) = rest.first() {
+                bail!("unknown argument for init: {}", arg);
+            }
+            Ok(Command::Init)
+        }
+        "explain" => match rest {
+            [path] => Ok(Command::Explain {
+                path: PathBuf::from(path),
+            }),
+            _ => bail!("usage: rufio explain <path>"),
+        },
+        "version" | "--version" => {
+            if let Some(arg) = rest.first() {
+                bail!("unknown argument for version: {}", arg);
+            }
+            Ok(Command::Version)
+        }
+        other => bail!("unknown subcommand: {}", other),
+    }
+}
+
+/// If `arg` is `--name value` or `--name=value`, return the value.
+fn flag_value<'a>(
+    arg: &'a str,
+    name: &str,
+    rest: &mut impl Iterator<Item = &'a String>,
+) -> Result<Option<&'a str>> {
+    if let Some(value) = arg.strip_prefix(name).and_then(|v| v.strip_prefix('=')) {
+        return Ok(Some(value));
+    }
+    if arg != name {
+        return Ok(None);
+    }
+    match rest.next() {
+        Some(value) => Ok(Some(value.as_str())),
+        None => bail!("{} requires a value", name),
+    }
+}
+
+fn parse_format(value: &str) -> Result<OutputFormat> {
+    match value {
+        "text" => Ok(OutputFormat::Text),
+        "json" => Ok(OutputFormat::Json),
+        other => bail!("unknown format '{}' (expected text or json)", other),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_no_args_is_hook() {
+        assert_eq!(parse_args(&[]).unwrap(), Command::Hook);
+    }
+
+    #[test]
+    fn test_check_format() {
+        assert_eq!(
+            parse_args(&args(&["check"])).unwrap(),
+            Command::Check {
+                format: OutputFormat::Text,
+                config: None,
+            }
+        );
+        assert_eq!(
+            parse_args(&args(&["check", "--format", "json"])).unwrap(),
+            Command::Check {
+                format: OutputFormat::Json,
+                config: None,
+            }
+        );
+        assert_eq!(
+            parse_args(&args(&["check", "--format=json"])).unwrap(),
+            Command::Check {
+                format: OutputFormat::Json,
+                config: None,
+            }
+        );
+    }
+
+    #[test]
+    fn test_check_config() {
+        assert_eq!(
+            parse_args(&args(&[
+                "check",
+                "--config",
+                "ci/rufio.yaml",
+                "--format=json"
+            ]))
+            .unwrap(),
+            Command::Check {
+                format: OutputFormat::Json,
+                config: Some(PathBuf::from("ci/rufio.yaml")),
+            }
+        );
+        assert!(parse_args(&args(&["check", "--config"])).is_err());
+    }
+
+    #[test]
+    fn test_init() {
+        assert_eq!(parse_args(&args(&["init"])).unwrap(), Command::Init);
+        assert!(parse_args(&args(&["init", "extra"])).is_err());
+    }
+
+    #[test]
+    fn test_explain() {
+        assert_eq!(
+            parse_args(&args(&["explain", "src/main.rs"])).unwrap(),
+            Command::Explain {
+                path: PathBuf::from("src/main.rs"),
+            }
+        );
+        assert!(parse_args(&args(&["explain"])).is_err());
+        assert!(parse_args(&args(&["explain", "a", "b"])).is_err());
+    }
+
+    #[test]
+    fn test_version() {
+        assert_eq!(parse_args(&args(&["version"])).unwrap(), Command::Version);
+        assert_eq!(parse_args(&args(&["--version"])).unwrap(), Command::Version);
+        assert!(parse_args(&args(&["version", "extra"])).is_err());
+    }
+
+    #[test]
+    fn test_invalid_args() {
+        assert!(parse_args(&args(&["bogus"])).is_err());
+        assert!(parse_args(&args(&["check", "--format"])).is_err());
+        assert!(parse_args(&args(&["check", "--format", "xml"])).is_err());
+    }
+}
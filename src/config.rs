@@ -1,27 +1,188 @@
 use anyhow::{bail, Context, Result};
-use serde::Deserialize;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::collections::HashMap;
 use std::fs;
 use std::path::{Path, PathBuf};
 use tracing::warn;
 
-const CONFIG_FILENAME: &str = "rufio-hooks.yaml";
+pub const CONFIG_FILENAME: &str = "rufio-hooks.yaml";
 
 /// Conditions that trigger a check
 #[derive(Debug, Clone, Deserialize)]
 pub struct When {
-    /// Glob pattern for files that trigger this check (relative to config dir)
-    pub paths_changed: String,
-    /// Optional: check only applies if this path exists (relative to config dir)
+    /// Glob pattern(s) for files that trigger this check (relative to config dir).
+    /// Accepts a single string or a list; any match triggers the check.
+    #[serde(deserialize_with = "string_or_list")]
+    pub paths_changed: Vec<String>,
+    /// Optional: check only applies if this path exists (relative to config dir).
+    /// May be a glob, in which case at least one file must match.
     pub path_exists: Option<String>,
+    /// Optional: check only applies on branches matching this glob
+    pub branch: Option<String>,
+    /// Optional: check never applies on branches matching any of these globs
+    pub skip_branches: Option<Vec<String>>,
+    /// Optional: required commands only count when run in this directory,
+    /// i.e. after a `cd` into it (relative to the session's cwd)
+    pub cwd_prefix: Option<String>,
+}
+
+/// Deserializes either a single string or a list of strings into a Vec
+fn string_or_list<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<String>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum StringOrList {
+        One(String),
+        Many(Vec<String>),
+    }
+
+    Ok(match StringOrList::deserialize(deserializer)? {
+        StringOrList::One(s) => vec![s],
+        StringOrList::Many(v) => v,
+    })
 }
 
 /// Actions required when check triggers - mutually exclusive
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Default, Deserialize)]
 pub struct Then {
     /// Commands that must ALL run after the last matching edit
     pub ensure_commands: Option<Vec<String>>,
+    /// Alternative commands that also satisfy an ensure_commands entry,
+    /// e.g. `cargo test: [just test, make test]`
+    pub command_aliases: Option<HashMap<String, Vec<String>>>,
     /// At least one of these paths must have been edited this session
     pub ensure_changed: Option<Vec<String>>,
+    /// None of these commands may have been run this session
+    pub forbid_commands: Option<Vec<String>>,
+    /// None of these paths may have been changed
+    pub ensure_no_changes: Option<Vec<String>>,
+    /// These paths must show nothing modified or untracked in git status
+    pub require_clean: Option<Vec<String>>,
+    /// Formatters that must run after the last matching edit, with no
+    /// matching edits after them
+    pub format_commands: Option<Vec<String>>,
+    /// These paths must exist on disk, whether or not they changed
+    pub ensure_exists: Option<Vec<String>>,
+    /// Custom failure reason; `{missing}` is replaced with the unmet items
+    pub message: Option<String>,
+    /// Only consider transcript events since the most recent user prompt
+    #[serde(default)]
+    pub current_turn_only: bool,
+    /// Don't count commands sent to the background (`cmd &`, `disown`)
+    /// as having run
+    #[serde(default)]
+    pub ignore_background: bool,
+    /// ensure_commands must run in the listed order, each after the previous
+    #[serde(default)]
+    pub ordered: bool,
+    /// How listed commands are matched against Bash commands
+    #[serde(rename = "match", default)]
+    pub match_mode: MatchMode,
+    /// Don't require ensure_changed when the diff of matching files only
+    /// touches comments, blank lines or indentation
+    #[serde(default)]
+    pub skip_trivial_changes: bool,
+    /// Only count an ensure_changed file as changed if its `version` value
+    /// is a strictly greater semver than at HEAD
+    #[serde(default)]
+    pub require_version_increase: bool,
+}
+
+impl Then {
+    /// Every action paired with whether it is set, in declaration order
+    fn actions(&self) -> [(&'static str, bool); 7] {
+        [
+            ("then.ensure_commands", self.ensure_commands.is_some()),
+            ("then.ensure_changed", self.ensure_changed.is_some()),
+            ("then.forbid_commands", self.forbid_commands.is_some()),
+            ("then.ensure_no_changes", self.ensure_no_changes.is_some()),
+            ("then.require_clean", self.require_clean.is_some()),
+            ("then.format_commands", self.format_commands.is_some()),
+            ("then.ensure_exists", self.ensure_exists.is_some()),
+        ]
+    }
+}
+
+/// How a configured command is found in a Bash command
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MatchMode {
+    /// The command appears verbatim anywhere in the Bash command (default)
+    #[default]
+    Substring,
+    /// Every whitespace-separated word of the command appears in one step of
+    /// the Bash command, in any order, so reordered flags still match
+    Tokens,
+}
+
+/// How a failing check is reported
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Level {
+    /// Block Claude from stopping until the check passes
+    #[default]
+    Block,
+    /// Report the failure as a system message without blocking
+    Warn,
+}
+
+/// Hook events a check can run on
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HookEvent {
+    /// When Claude finishes responding (default)
+    #[default]
+    Stop,
+    /// Right after an Edit/Write/MultiEdit, against just the edited file; failures
+    /// are reported as a non-blocking system message
+    PostToolUse,
+}
+
+fn default_on() -> Vec<HookEvent> {
+    vec![HookEvent::Stop]
+}
+
+/// Built-in tools whose transcript events checks understand
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum BuiltinTool {
+    Edit,
+    Write,
+    MultiEdit,
+    Bash,
+}
+
+impl BuiltinTool {
+    pub fn name(self) -> &'static str {
+        match self {
+            BuiltinTool::Edit => "Edit",
+            BuiltinTool::Write => "Write",
+            BuiltinTool::MultiEdit => "MultiEdit",
+            BuiltinTool::Bash => "Bash",
+        }
+    }
+
+    /// Input key holding the file path (Edit/Write/MultiEdit) or command (Bash)
+    pub fn default_key(self) -> &'static str {
+        match self {
+            BuiltinTool::Edit | BuiltinTool::Write | BuiltinTool::MultiEdit => "file_path",
+            BuiltinTool::Bash => "command",
+        }
+    }
+}
+
+/// Treat a custom tool (e.g. from an MCP server) as a built-in one,
+/// e.g. `mcp__fs__write_file: {as: Write, key: path}`
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct ToolMapping {
+    #[serde(rename = "as")]
+    pub tool: BuiltinTool,
+    /// Input key holding the path or command, if not the built-in tool's
+    pub key: Option<String>,
+}
+
+impl ToolMapping {
+    pub fn key(&self) -> &str {
+        self.key.as_deref().unwrap_or(self.tool.default_key())
+    }
 }
 
 /// A single check definition
@@ -33,6 +194,27 @@ pub struct Check {
     pub when: When,
     /// Required actions
     pub then: Then,
+    /// Whether a failure blocks (default) or only warns
+    #[serde(default)]
+    pub level: Level,
+    /// Names of checks that must pass for this one to be reported
+    #[serde(default)]
+    pub depends_on: Vec<String>,
+    /// Hook events that run this check
+    #[serde(default = "default_on")]
+    pub on: Vec<HookEvent>,
+    /// Directory of the ancestor config this check was inherited from;
+    /// None for checks defined in the loaded config itself
+    #[serde(skip)]
+    pub inherited_from: Option<PathBuf>,
+}
+
+impl Check {
+    /// Directory this check's paths are relative to, given the directory
+    /// of the config it was loaded through
+    pub fn config_dir<'a>(&'a self, loaded_dir: &'a Path) -> &'a Path {
+        self.inherited_from.as_deref().unwrap_or(loaded_dir)
+    }
 }
 
 /// Raw configuration structure (as parsed from YAML)
@@ -42,6 +224,29 @@ struct RufioConfigRaw {
     presets: Option<Vec<String>>,
     /// Custom check definitions
     checks: Option<Vec<Check>>,
+    /// Presets defined inline, resolvable by name from `presets`
+    define_presets: Option<HashMap<String, PresetFile>>,
+    /// Send desktop notifications when Claude stops or needs input
+    #[serde(default)]
+    notifications: bool,
+    /// Fail on unset ${VAR} references in commands instead of leaving them literal
+    #[serde(default)]
+    strict: bool,
+    /// Extend configs in parent directories (up to the repo root) instead of replacing them
+    #[serde(default)]
+    inherit: bool,
+    /// How multiple failure reasons are combined in hook output
+    #[serde(default)]
+    reason_format: ReasonFormat,
+    /// Custom tool names to read from the transcript as built-in tools
+    #[serde(default)]
+    tool_mappings: HashMap<String, ToolMapping>,
+    /// Turn rufio off for this directory: no checks run
+    #[serde(default)]
+    disabled: bool,
+    /// Write each Stop's results to rufio-result-{session_id}.json in the runtime dir
+    #[serde(default)]
+    write_results: bool,
 }
 
 /// Preset file structure
@@ -50,10 +255,46 @@ struct PresetFile {
     checks: Vec<Check>,
 }
 
+/// How multiple failure reasons are combined in hook output
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReasonFormat {
+    /// One line, reasons separated by " | "
+    #[default]
+    Inline,
+    /// One "- " bullet per line
+    List,
+}
+
+impl ReasonFormat {
+    pub fn join(self, reasons: &[&str]) -> String {
+        match self {
+            ReasonFormat::Inline => reasons.join(" | "),
+            ReasonFormat::List => reasons
+                .iter()
+                .map(|r| format!("- {}", r))
+                .collect::<Vec<_>>()
+                .join("\n"),
+        }
+    }
+}
+
 /// Resolved configuration (presets expanded, checks always defined)
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct RufioConfig {
     pub checks: Vec<Check>,
+    /// Send desktop notifications when Claude stops or needs input
+    pub notifications: bool,
+    /// Extend configs in parent directories instead of replacing them
+    pub inherit: bool,
+    /// How multiple failure reasons are combined in hook output
+    pub reason_format: ReasonFormat,
+    /// Custom tool names to read from the transcript as built-in tools
+    pub tool_mappings: HashMap<String, ToolMapping>,
+    /// Turn rufio off for this directory: no checks run
+    pub disabled: bool,
+    /// Write each Stop's results to rufio-result-{session_id}.json in the runtime dir
+    pub write_results: bool,
 }
 
 /// Parsed config with its location
@@ -64,11 +305,25 @@ pub struct LoadedConfig {
     pub config_dir: PathBuf,
 }
 
-/// Resolves preset names to their check definitions from XDG config
-fn resolve_presets(preset_names: &[String], config_path: &Path) -> Result<Vec<Check>> {
+/// Resolves preset names to their check definitions.
+/// Inline definitions from the config take precedence over XDG config.
+fn resolve_presets(
+    preset_names: &[String],
+    inline_presets: Option<&HashMap<String, PresetFile>>,
+    config_path: &Path,
+) -> Result<Vec<Check>> {
     let mut checks = Vec::new();
 
     for name in preset_names {
+        if let Some(inline) = inline_presets.and_then(|presets| presets.get(name)) {
+            // Inline presets are user-written, so validate them like user checks
+            for check in &inline.checks {
+                validate_check(check, config_path)?;
+            }
+            checks.extend(inline.checks.iter().cloned());
+            continue;
+        }
+
         match load_preset_from_xdg(name)? {
             Some(xdg_checks) => checks.extend(xdg_checks),
             None => {
@@ -86,8 +341,8 @@ fn resolve_presets(preset_names: &[String], config_path: &Path) -> Result<Vec<Ch
     Ok(checks)
 }
 
-/// Get the expected path for a preset in XDG config
-fn get_preset_path(name: &str) -> PathBuf {
+/// $XDG_CONFIG_HOME/rufio, falling back to ~/.config/rufio
+fn rufio_config_home() -> PathBuf {
     let xdg_config = std::env::var("XDG_CONFIG_HOME")
         .map(PathBuf::from)
         .unwrap_or_else(|_| {
@@ -95,12 +350,21 @@ fn get_preset_path(name: &str) -> PathBuf {
             PathBuf::from(home).join(".config")
         });
 
-    xdg_config
-        .join("rufio")
+    xdg_config.join("rufio")
+}
+
+/// Get the expected path for a preset in XDG config
+fn get_preset_path(name: &str) -> PathBuf {
+    rufio_config_home()
         .join("presets")
         .join(format!("{}.yaml", name))
 }
 
+/// User-level config applied when a repo has no rufio-hooks.yaml
+pub fn global_config_path() -> PathBuf {
+    rufio_config_home().join("config.yaml")
+}
+
 /// Try to load a preset from $XDG_CONFIG_HOME/rufio/presets/{name}.yaml
 fn load_preset_from_xdg(name: &str) -> Result<Option<Vec<Check>>> {
     let preset_path = get_preset_path(name);
@@ -115,6 +379,10 @@ fn load_preset_from_xdg(name: &str) -> Result<Option<Vec<Check>>> {
     let preset: PresetFile = serde_yaml::from_str(&content)
         .with_context(|| format!("Failed to parse preset file: {}", preset_path.display()))?;
 
+    for check in &preset.checks {
+        validate_globs(check, &preset_path)?;
+    }
+
     Ok(Some(preset.checks))
 }
 
@@ -126,27 +394,289 @@ fn validate_check(check: &Check, config_path: &Path) -> Result<()> {
             config_path.display()
         );
     }
-    if check.when.paths_changed.is_empty() {
+    if check.when.paths_changed.is_empty() || check.when.paths_changed.iter().any(String::is_empty)
+    {
         bail!(
             "Invalid config at {}: check '{}' missing 'when.paths_changed'",
             config_path.display(),
             check.name
         );
     }
-    if check.then.ensure_commands.is_none() && check.then.ensure_changed.is_none() {
+    validate_globs(check, config_path)?;
+    let actions = check.then.actions();
+    let set: Vec<&str> = actions
+        .iter()
+        .filter_map(|(name, is_set)| is_set.then_some(*name))
+        .collect();
+    if set.is_empty() {
+        let names: Vec<&str> = actions.iter().map(|(name, _)| *name).collect();
+        bail!(
+            "Invalid config at {}: check '{}' must have one of '{}'",
+            config_path.display(),
+            check.name,
+            names.join("', '")
+        );
+    }
+    if set.len() > 1 {
+        bail!(
+            "Invalid config at {}: check '{}' cannot combine '{}'",
+            config_path.display(),
+            check.name,
+            set.join("' and '")
+        );
+    }
+    if check.then.command_aliases.is_some()
+        && check.then.ensure_commands.is_none()
+        && check.then.format_commands.is_none()
+    {
         bail!(
-            "Invalid config at {}: check '{}' must have 'then.ensure_commands' or 'then.ensure_changed'",
+            "Invalid config at {}: check '{}' has 'then.command_aliases' without 'then.ensure_commands' or 'then.format_commands'",
             config_path.display(),
             check.name
         );
     }
-    if check.then.ensure_commands.is_some() && check.then.ensure_changed.is_some() {
+    if check.then.ordered && check.then.ensure_commands.is_none() {
         bail!(
-            "Invalid config at {}: check '{}' cannot have both 'then.ensure_commands' and 'then.ensure_changed'",
+            "Invalid config at {}: check '{}' has 'then.ordered' without 'then.ensure_commands'",
             config_path.display(),
             check.name
         );
     }
+    if check.on.contains(&HookEvent::PostToolUse) {
+        // PostToolUse doesn't read the transcript, so there are no commands to see
+        let commands = [
+            ("then.ensure_commands", check.then.ensure_commands.is_some()),
+            ("then.forbid_commands", check.then.forbid_commands.is_some()),
+            ("then.format_commands", check.then.format_commands.is_some()),
+        ];
+        if let Some((action, _)) = commands.iter().find(|(_, is_set)| *is_set) {
+            bail!(
+                "Invalid config at {}: check '{}' has '{}', which needs the transcript and can't run 'on: [post_tool_use]'",
+                config_path.display(),
+                check.name,
+                action
+            );
+        }
+    }
+    for (option, is_set) in [
+        ("then.skip_trivial_changes", check.then.skip_trivial_changes),
+        (
+            "then.require_version_increase",
+            check.then.require_version_increase,
+        ),
+    ] {
+        if is_set && check.then.ensure_changed.is_none() {
+            bail!(
+                "Invalid config at {}: check '{}' has '{}' without 'then.ensure_changed'",
+                config_path.display(),
+                check.name,
+                option
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Ensures every glob in a check's `when` block compiles, so a typo fails at load
+/// rather than surfacing as a block reason at Stop.
+fn validate_globs(check: &Check, config_path: &Path) -> Result<()> {
+    let globs = check
+        .when
+        .paths_changed
+        .iter()
+        .map(|glob| ("when.paths_changed", glob))
+        .chain(check.when.branch.iter().map(|glob| ("when.branch", glob)))
+        .chain(
+            check
+                .when
+                .skip_branches
+                .iter()
+                .flatten()
+                .map(|glob| ("when.skip_branches", glob)),
+        );
+    for (field, glob) in globs {
+        if let Err(e) = glob::Pattern::new(glob) {
+            bail!(
+                "Invalid config at {}: check '{}' has invalid glob '{}' in '{}': {}",
+                config_path.display(),
+                check.name,
+                glob,
+                field,
+                e.msg
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Expands `${VAR}` references in a string using `lookup`.
+/// Unknown variables are left as-is and their names returned alongside the result.
+fn interpolate_vars(input: &str, lookup: impl Fn(&str) -> Option<String>) -> (String, Vec<String>) {
+    let mut output = String::with_capacity(input.len());
+    let mut missing = Vec::new();
+    let mut rest = input;
+
+    while let Some(start) = rest.find("${") {
+        output.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        let Some(end) = after.find('}') else {
+            // Unterminated reference, keep the remainder verbatim
+            rest = &rest[start..];
+            break;
+        };
+
+        let name = &after[..end];
+        match lookup(name) {
+            Some(value) => output.push_str(&value),
+            None => {
+                missing.push(name.to_string());
+                output.push_str(&rest[start..start + 2 + end + 1]);
+            }
+        }
+        rest = &after[end + 1..];
+    }
+
+    output.push_str(rest);
+    (output, missing)
+}
+
+/// Expands environment variables in each check's command lists
+/// (ensure_commands, forbid_commands and format_commands) and in both sides
+/// of its command_aliases, so aliases keep matching the expanded commands.
+/// In strict mode an unset variable is a config error.
+fn interpolate_commands(checks: &mut [Check], strict: bool, config_path: &Path) -> Result<()> {
+    for check in checks {
+        let expand = |cmd: &str| -> Result<String> {
+            let (expanded, missing) = interpolate_vars(cmd, |name| std::env::var(name).ok());
+            if strict {
+                if let Some(name) = missing.first() {
+                    bail!(
+                        "Invalid config at {}: check '{}' references unset environment variable '{}'",
+                        config_path.display(),
+                        check.name,
+                        name
+                    );
+                }
+            }
+            Ok(expanded)
+        };
+
+        let then = &mut check.then;
+        for commands in [
+            &mut then.ensure_commands,
+            &mut then.forbid_commands,
+            &mut then.format_commands,
+        ]
+        .into_iter()
+        .flatten()
+        {
+            *commands = commands
+                .iter()
+                .map(|cmd| expand(cmd))
+                .collect::<Result<Vec<_>>>()?;
+        }
+
+        if let Some(aliases) = check.then.command_aliases.as_ref() {
+            let mut expanded = HashMap::with_capacity(aliases.len());
+            for (command, alternatives) in aliases {
+                let alternatives = alternatives
+                    .iter()
+                    .map(|alt| expand(alt))
+                    .collect::<Result<Vec<_>>>()?;
+                expanded.insert(expand(command)?, alternatives);
+            }
+            check.then.command_aliases = Some(expanded);
+        }
+    }
+    Ok(())
+}
+
+/// Merges base checks (e.g. presets) with overriding checks (e.g. user checks):
+/// base first, then overrides. An override replaces any base check with the same name.
+fn merge_checks(base_checks: Vec<Check>, override_checks: &[Check]) -> Vec<Check> {
+    let mut merged: Vec<Check> = base_checks
+        .into_iter()
+        .filter(|base| !override_checks.iter().any(|o| o.name == base.name))
+        .collect();
+    merged.extend(override_checks.iter().cloned());
+    merged
+}
+
+/// Orders checks so each comes after the checks it depends on, otherwise
+/// keeping config order. Returns indices into `checks`.
+/// Unknown dependency names are ignored; a cycle is an error.
+pub fn dependency_order(checks: &[Check]) -> Result<Vec<usize>> {
+    #[derive(Clone, Copy, PartialEq)]
+    enum Mark {
+        Unvisited,
+        InProgress,
+        Done,
+    }
+
+    fn visit(
+        index: usize,
+        checks: &[Check],
+        marks: &mut [Mark],
+        path: &mut Vec<usize>,
+        order: &mut Vec<usize>,
+    ) -> Result<()> {
+        match marks[index] {
+            Mark::Done => return Ok(()),
+            Mark::InProgress => {
+                let start = path.iter().position(|&i| i == index).unwrap_or(0);
+                let cycle: Vec<&str> = path[start..]
+                    .iter()
+                    .chain(std::iter::once(&index))
+                    .map(|&i| checks[i].name.as_str())
+                    .collect();
+                bail!("dependency cycle: {}", cycle.join(" -> "));
+            }
+            Mark::Unvisited => {}
+        }
+
+        marks[index] = Mark::InProgress;
+        path.push(index);
+        for dep in &checks[index].depends_on {
+            if let Some(dep_index) = checks.iter().position(|c| &c.name == dep) {
+                visit(dep_index, checks, marks, path, order)?;
+            }
+        }
+        path.pop();
+        marks[index] = Mark::Done;
+        order.push(index);
+        Ok(())
+    }
+
+    let mut marks = vec![Mark::Unvisited; checks.len()];
+    let mut order = Vec::with_capacity(checks.len());
+    for index in 0..checks.len() {
+        visit(index, checks, &mut marks, &mut Vec::new(), &mut order)?;
+    }
+    Ok(order)
+}
+
+/// Checks that depends_on names exist (unless inheriting, where they may be
+/// defined by an ancestor config) and contain no cycles.
+fn validate_dependencies(checks: &[Check], inherit: bool, config_path: &Path) -> Result<()> {
+    if !inherit {
+        for check in checks {
+            if let Some(dep) = check
+                .depends_on
+                .iter()
+                .find(|dep| !checks.iter().any(|c| &c.name == *dep))
+            {
+                bail!(
+                    "Invalid config at {}: check '{}' depends on unknown check '{}'",
+                    config_path.display(),
+                    check.name,
+                    dep
+                );
+            }
+        }
+    }
+
+    dependency_order(checks)
+        .with_context(|| format!("Invalid config at {}", config_path.display()))?;
     Ok(())
 }
 
@@ -161,18 +691,16 @@ pub fn load_config(config_path: &Path) -> Result<RufioConfig> {
 
     // Resolve presets first
     let preset_checks = if let Some(presets) = &parsed.presets {
-        resolve_presets(presets, config_path)?
+        resolve_presets(presets, parsed.define_presets.as_ref(), config_path)?
     } else {
         Vec::new()
     };
 
     let user_checks = parsed.checks.unwrap_or_default();
 
-    // Merge: presets first, then user checks
-    let mut merged_checks = preset_checks;
-    merged_checks.extend(user_checks.iter().cloned());
+    let mut merged_checks = merge_checks(preset_checks, &user_checks);
 
-    if merged_checks.is_empty() {
+    if merged_checks.is_empty() && !parsed.disabled {
         bail!(
             "Invalid config at {}: no checks defined (add 'presets' or 'checks')",
             config_path.display()
@@ -184,16 +712,68 @@ pub fn load_config(config_path: &Path) -> Result<RufioConfig> {
         validate_check(check, config_path)?;
     }
 
+    validate_dependencies(&merged_checks, parsed.inherit, config_path)?;
+    interpolate_commands(&mut merged_checks, parsed.strict, config_path)?;
+
     Ok(RufioConfig {
         checks: merged_checks,
+        notifications: parsed.notifications,
+        inherit: parsed.inherit,
+        reason_format: parsed.reason_format,
+        tool_mappings: parsed.tool_mappings,
+        disabled: parsed.disabled,
+        write_results: parsed.write_results,
+    })
+}
+
+/// Loads an explicitly chosen config file (e.g. from RUFIO_CONFIG), bypassing
+/// discovery. Its checks are evaluated relative to `config_dir`.
+pub fn load_config_file(config_path: &Path, config_dir: &Path) -> Result<LoadedConfig> {
+    Ok(LoadedConfig {
+        config: load_config(config_path)?,
+        config_dir: config_dir.to_path_buf(),
     })
 }
 
 /// Finds the nearest rufio-hooks.yaml config file by walking up from a directory.
 /// Stops at the repository root (does not leave the repo).
 ///
+/// Falls back to the global config (see `global_config_path`) when no
+/// config exists between start_dir and repo_root.
+///
 /// Returns LoadedConfig if found, None otherwise.
 pub fn find_nearest_config(start_dir: &Path, repo_root: &Path) -> Option<LoadedConfig> {
+    find_config_with_fallback(start_dir, repo_root, &global_config_path())
+}
+
+fn find_config_with_fallback(
+    start_dir: &Path,
+    repo_root: &Path,
+    global_path: &Path,
+) -> Option<LoadedConfig> {
+    find_local_config(start_dir, repo_root).or_else(|| load_global_config(global_path, repo_root))
+}
+
+/// Loads the global config, evaluated relative to repo_root
+fn load_global_config(global_path: &Path, repo_root: &Path) -> Option<LoadedConfig> {
+    if !global_path.is_file() {
+        return None;
+    }
+
+    match load_config(global_path) {
+        Ok(config) => Some(LoadedConfig {
+            config,
+            config_dir: repo_root.to_path_buf(),
+        }),
+        Err(e) => {
+            warn!(path = %global_path.display(), error = %e, "failed to load global config");
+            None
+        }
+    }
+}
+
+/// Walks up from start_dir to repo_root looking for rufio-hooks.yaml
+pub fn find_local_config(start_dir: &Path, repo_root: &Path) -> Option<LoadedConfig> {
     let mut current = start_dir.to_path_buf();
 
     loop {
@@ -201,7 +781,10 @@ pub fn find_nearest_config(start_dir: &Path, repo_root: &Path) -> Option<LoadedC
 
         if config_path.exists() {
             match load_config(&config_path) {
-                Ok(config) => {
+                Ok(mut config) => {
+                    if config.inherit {
+                        inherit_ancestor_configs(&mut config, &current, repo_root);
+                    }
                     return Some(LoadedConfig {
                         config,
                         config_dir: current,
@@ -230,6 +813,33 @@ pub fn find_nearest_config(start_dir: &Path, repo_root: &Path) -> Option<LoadedC
     }
 }
 
+/// Merges checks from every config above `config_dir` (up to repo_root) into
+/// `config`. Nearer checks override ancestor checks with the same name.
+/// Inherited checks remember their ancestor's directory so their paths keep
+/// resolving relative to the config that defined them.
+fn inherit_ancestor_configs(config: &mut RufioConfig, config_dir: &Path, repo_root: &Path) {
+    let mut current = config_dir.to_path_buf();
+
+    while current != repo_root && current.pop() && current.starts_with(repo_root) {
+        let config_path = current.join(CONFIG_FILENAME);
+        if !config_path.exists() {
+            continue;
+        }
+
+        match load_config(&config_path) {
+            Ok(mut ancestor) => {
+                for check in &mut ancestor.checks {
+                    check.inherited_from = Some(current.clone());
+                }
+                config.checks = merge_checks(ancestor.checks, &config.checks);
+            }
+            Err(e) => {
+                warn!(path = %config_path.display(), error = %e, "failed to load inherited config");
+            }
+        }
+    }
+}
+
 /// Groups changed files by their nearest config.
 /// Returns a map of config_dir -> (LoadedConfig, files)
 pub fn group_files_by_config(
@@ -318,45 +928,131 @@ checks:
     }
 
     #[test]
-    fn test_load_config_empty_fails() {
+    fn test_load_config_notifications_default_off() {
         let temp = TempDir::new().unwrap();
         let config_path = temp.path().join(CONFIG_FILENAME);
-        fs::write(&config_path, "{}").unwrap();
+        fs::write(
+            &config_path,
+            r#"
+checks:
+  - name: test-check
+    when:
+      paths_changed: "**/*.rs"
+    then:
+      ensure_commands:
+        - cargo test
+"#,
+        )
+        .unwrap();
 
-        assert!(load_config(&config_path).is_err());
+        let config = load_config(&config_path).unwrap();
+        assert!(!config.notifications);
     }
 
     #[test]
-    fn test_load_config_both_then_fields_fails() {
+    fn test_load_config_with_notifications() {
         let temp = TempDir::new().unwrap();
         let config_path = temp.path().join(CONFIG_FILENAME);
         fs::write(
             &config_path,
             r#"
+notifications: true
 checks:
-  - name: bad-check
+  - name: test-check
+    when:
+      paths_changed: "**/*.rs"
+    then:
+      ensure_commands:
+        - cargo test
+"#,
+        )
+        .unwrap();
+
+        let config = load_config(&config_path).unwrap();
+        assert!(config.notifications);
+    }
+
+    #[test]
+    fn test_load_config_paths_changed_list() {
+        let temp = TempDir::new().unwrap();
+        let config_path = temp.path().join(CONFIG_FILENAME);
+        fs::write(
+            &config_path,
+            r#"
+checks:
+  - name: single
     when:
       paths_changed: "**/*.rs"
     then:
       ensure_commands:
         - cargo test
+  - name: multi
+    when:
+      paths_changed:
+        - "**/*.rs"
+        - "**/*.toml"
+        - build.rs
+    then:
+      ensure_commands:
+        - cargo build
+"#,
+        )
+        .unwrap();
+
+        let config = load_config(&config_path).unwrap();
+        assert_eq!(config.checks[0].when.paths_changed, vec!["**/*.rs"]);
+        assert_eq!(
+            config.checks[1].when.paths_changed,
+            vec!["**/*.rs", "**/*.toml", "build.rs"]
+        );
+    }
+
+    #[test]
+    fn test_load_config_inline_preset() {
+        let temp = TempDir::new().unwrap();
+        let config_path = temp.path().join(CONFIG_FILENAME);
+        fs::write(
+            &config_path,
+            r#"
+define_presets:
+  rust:
+    checks:
+      - name: cargo-test
+        when:
+          paths_changed: "**/*.rs"
+        then:
+          ensure_commands:
+            - cargo test
+  unused:
+    checks:
+      - name: never
+        when:
+          paths_changed: "**/*"
+        then:
+          ensure_commands:
+            - "true"
+presets:
+  - rust
+checks:
+  - name: version-bump
+    when:
+      paths_changed: "**/*.rs"
+    then:
       ensure_changed:
         - version.toml
 "#,
         )
         .unwrap();
 
-        assert!(load_config(&config_path).is_err());
+        let config = load_config(&config_path).unwrap();
+        let names: Vec<&str> = config.checks.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, vec!["cargo-test", "version-bump"]);
     }
 
     #[test]
-    fn test_find_nearest_config() {
+    fn test_load_config_command_aliases() {
         let temp = TempDir::new().unwrap();
-        let repo_root = temp.path();
-        let subdir = repo_root.join("src/lib");
-        fs::create_dir_all(&subdir).unwrap();
-
-        let config_path = repo_root.join(CONFIG_FILENAME);
+        let config_path = temp.path().join(CONFIG_FILENAME);
         fs::write(
             &config_path,
             r#"
@@ -367,52 +1063,788 @@ checks:
     then:
       ensure_commands:
         - cargo test
+      command_aliases:
+        cargo test:
+          - just test
+          - make test
 "#,
         )
         .unwrap();
 
-        let loaded = find_nearest_config(&subdir, repo_root);
-        assert!(loaded.is_some());
-        assert_eq!(loaded.unwrap().config_dir, repo_root);
+        let config = load_config(&config_path).unwrap();
+        let aliases = config.checks[0].then.command_aliases.as_ref().unwrap();
+        assert_eq!(aliases["cargo test"], vec!["just test", "make test"]);
     }
 
     #[test]
-    fn test_find_nearest_config_nested() {
+    fn test_load_config_empty_fails() {
         let temp = TempDir::new().unwrap();
-        let repo_root = temp.path();
-        let pkg_dir = repo_root.join("packages/foo");
-        let src_dir = pkg_dir.join("src");
-        fs::create_dir_all(&src_dir).unwrap();
+        let config_path = temp.path().join(CONFIG_FILENAME);
+        fs::write(&config_path, "{}").unwrap();
 
-        // Config at package level, not repo root
-        let config_path = pkg_dir.join(CONFIG_FILENAME);
+        assert!(load_config(&config_path).is_err());
+    }
+
+    #[test]
+    fn test_load_config_both_then_fields_fails() {
+        let temp = TempDir::new().unwrap();
+        let config_path = temp.path().join(CONFIG_FILENAME);
         fs::write(
             &config_path,
             r#"
 checks:
-  - name: pkg-check
+  - name: bad-check
     when:
-      paths_changed: "**/*.ts"
+      paths_changed: "**/*.rs"
     then:
       ensure_commands:
-        - pnpm test
+        - cargo test
+      ensure_changed:
+        - version.toml
 "#,
         )
         .unwrap();
 
-        let loaded = find_nearest_config(&src_dir, repo_root);
-        assert!(loaded.is_some());
-        assert_eq!(loaded.unwrap().config_dir, pkg_dir);
+        assert!(load_config(&config_path).is_err());
     }
 
     #[test]
-    fn test_find_nearest_config_none() {
+    fn test_load_config_invalid_glob_fails() {
         let temp = TempDir::new().unwrap();
-        let repo_root = temp.path();
-        let subdir = repo_root.join("src");
-        fs::create_dir_all(&subdir).unwrap();
-
-        let loaded = find_nearest_config(&subdir, repo_root);
-        assert!(loaded.is_none());
+        let config_path = temp.path().join(CONFIG_FILENAME);
+        fs::write(
+            &config_path,
+            r#"
+checks:
+  - name: typo-check
+    when:
+      paths_changed: "src/[*.rs"
+    then:
+      ensure_commands:
+        - cargo test
+"#,
+        )
+        .unwrap();
+
+        let err = load_config(&config_path).unwrap_err().to_string();
+        assert!(err.contains(&config_path.display().to_string()));
+        assert!(err.contains("typo-check"));
+        assert!(err.contains("src/[*.rs"));
+    }
+
+    fn parse_checks(yaml: &str) -> Vec<Check> {
+        serde_yaml::from_str::<PresetFile>(yaml).unwrap().checks
+    }
+
+    #[test]
+    fn test_merge_checks_user_overrides_preset() {
+        // As if resolved from presets: ["cargo"]
+        let preset_checks = parse_checks(
+            r#"
+checks:
+  - name: cargo-checks
+    when:
+      paths_changed: "**/*.rs"
+    then:
+      ensure_commands:
+        - cargo test
+        - cargo clippy
+  - name: cargo-fmt
+    when:
+      paths_changed: "**/*.rs"
+    then:
+      ensure_commands:
+        - cargo fmt
+"#,
+        );
+        let user_checks = parse_checks(
+            r#"
+checks:
+  - name: cargo-checks
+    when:
+      paths_changed: "src/**/*.rs"
+    then:
+      ensure_commands:
+        - cargo nextest run
+"#,
+        );
+
+        let merged = merge_checks(preset_checks, &user_checks);
+        let names: Vec<&str> = merged.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, vec!["cargo-fmt", "cargo-checks"]);
+        assert_eq!(merged[1].when.paths_changed, vec!["src/**/*.rs"]);
+        assert_eq!(
+            merged[1].then.ensure_commands,
+            Some(vec!["cargo nextest run".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_interpolate_vars() {
+        let lookup = |name: &str| (name == "FOO").then(|| "pytest -x".to_string());
+
+        let (expanded, missing) = interpolate_vars("run ${FOO} now", lookup);
+        assert_eq!(expanded, "run pytest -x now");
+        assert!(missing.is_empty());
+
+        let (expanded, missing) = interpolate_vars("${BAR} && ${FOO}", lookup);
+        assert_eq!(expanded, "${BAR} && pytest -x");
+        assert_eq!(missing, vec!["BAR"]);
+
+        let (expanded, missing) = interpolate_vars("echo $FOO ${FOO", lookup);
+        assert_eq!(expanded, "echo $FOO ${FOO");
+        assert!(missing.is_empty());
+    }
+
+    #[test]
+    fn test_load_config_interpolates_commands() {
+        std::env::set_var("RUFIO_TEST_INTERPOLATE_CMD", "cargo nextest run");
+        let temp = TempDir::new().unwrap();
+        let config_path = temp.path().join(CONFIG_FILENAME);
+        fs::write(
+            &config_path,
+            r#"
+checks:
+  - name: test
+    when:
+      paths_changed: "**/*.rs"
+    then:
+      ensure_commands:
+        - ${RUFIO_TEST_INTERPOLATE_CMD}
+        - ${RUFIO_TEST_INTERPOLATE_UNSET}
+"#,
+        )
+        .unwrap();
+
+        let config = load_config(&config_path).unwrap();
+        assert_eq!(
+            config.checks[0].then.ensure_commands,
+            Some(vec![
+                "cargo nextest run".to_string(),
+                "${RUFIO_TEST_INTERPOLATE_UNSET}".to_string()
+            ])
+        );
+    }
+
+    #[test]
+    fn test_load_config_interpolates_command_aliases() {
+        std::env::set_var("RUFIO_TEST_INTERPOLATE_ALIAS", "cargo nextest run");
+        let temp = TempDir::new().unwrap();
+        let config_path = temp.path().join(CONFIG_FILENAME);
+        fs::write(
+            &config_path,
+            r#"
+checks:
+  - name: test
+    when:
+      paths_changed: "**/*.rs"
+    then:
+      ensure_commands:
+        - ${RUFIO_TEST_INTERPOLATE_ALIAS}
+      command_aliases:
+        ${RUFIO_TEST_INTERPOLATE_ALIAS}:
+          - just ${RUFIO_TEST_INTERPOLATE_ALIAS}
+"#,
+        )
+        .unwrap();
+
+        let config = load_config(&config_path).unwrap();
+        let aliases = config.checks[0].then.command_aliases.as_ref().unwrap();
+        assert_eq!(
+            aliases.get("cargo nextest run"),
+            Some(&vec!["just cargo nextest run".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_load_config_interpolates_forbid_and_format_commands() {
+        std::env::set_var("RUFIO_TEST_INTERPOLATE_TOOL", "cargo");
+        let temp = TempDir::new().unwrap();
+        let config_path = temp.path().join(CONFIG_FILENAME);
+        fs::write(
+            &config_path,
+            r#"
+checks:
+  - name: no-publish
+    when:
+      paths_changed: "**/*.rs"
+    then:
+      forbid_commands:
+        - ${RUFIO_TEST_INTERPOLATE_TOOL} publish
+  - name: fmt
+    when:
+      paths_changed: "**/*.rs"
+    then:
+      format_commands:
+        - ${RUFIO_TEST_INTERPOLATE_TOOL} fmt
+"#,
+        )
+        .unwrap();
+
+        let config = load_config(&config_path).unwrap();
+        assert_eq!(
+            config.checks[0].then.forbid_commands,
+            Some(vec!["cargo publish".to_string()])
+        );
+        assert_eq!(
+            config.checks[1].then.format_commands,
+            Some(vec!["cargo fmt".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_load_config_strict_unset_var_fails() {
+        let temp = TempDir::new().unwrap();
+        let config_path = temp.path().join(CONFIG_FILENAME);
+        fs::write(
+            &config_path,
+            r#"
+strict: true
+checks:
+  - name: test
+    when:
+      paths_changed: "**/*.rs"
+    then:
+      ensure_commands:
+        - ${RUFIO_TEST_INTERPOLATE_UNSET}
+"#,
+        )
+        .unwrap();
+
+        let err = load_config(&config_path).unwrap_err();
+        assert!(err.to_string().contains("RUFIO_TEST_INTERPOLATE_UNSET"));
+    }
+
+    #[test]
+    fn test_load_config_forbid_commands() {
+        let temp = TempDir::new().unwrap();
+        let config_path = temp.path().join(CONFIG_FILENAME);
+        fs::write(
+            &config_path,
+            r#"
+checks:
+  - name: no-force-push
+    when:
+      paths_changed: "**/*"
+    then:
+      forbid_commands:
+        - git push --force
+"#,
+        )
+        .unwrap();
+
+        let config = load_config(&config_path).unwrap();
+        assert_eq!(
+            config.checks[0].then.forbid_commands,
+            Some(vec!["git push --force".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_load_config_forbid_commands_exclusive() {
+        let temp = TempDir::new().unwrap();
+        let config_path = temp.path().join(CONFIG_FILENAME);
+        fs::write(
+            &config_path,
+            r#"
+checks:
+  - name: bad-check
+    when:
+      paths_changed: "**/*.rs"
+    then:
+      ensure_commands:
+        - cargo test
+      forbid_commands:
+        - rm -rf
+"#,
+        )
+        .unwrap();
+
+        let err = load_config(&config_path).unwrap_err();
+        assert!(err.to_string().contains("then.forbid_commands"));
+    }
+
+    #[test]
+    fn test_load_config_ensure_no_changes_exclusive() {
+        let temp = TempDir::new().unwrap();
+        let config_path = temp.path().join(CONFIG_FILENAME);
+        fs::write(
+            &config_path,
+            r#"
+checks:
+  - name: bad-check
+    when:
+      paths_changed: "**/*.rs"
+    then:
+      ensure_changed:
+        - version.toml
+      ensure_no_changes:
+        - Cargo.lock
+"#,
+        )
+        .unwrap();
+
+        let err = load_config(&config_path).unwrap_err();
+        assert!(err.to_string().contains("then.ensure_no_changes"));
+    }
+
+    #[test]
+    fn test_load_config_require_clean() {
+        let temp = TempDir::new().unwrap();
+        let config_path = temp.path().join(CONFIG_FILENAME);
+        fs::write(
+            &config_path,
+            r#"
+checks:
+  - name: release
+    when:
+      paths_changed: "src/**"
+    then:
+      require_clean:
+        - dist
+"#,
+        )
+        .unwrap();
+        let config = load_config(&config_path).unwrap();
+        assert_eq!(
+            config.checks[0].then.require_clean,
+            Some(vec!["dist".to_string()])
+        );
+
+        fs::write(
+            &config_path,
+            r#"
+checks:
+  - name: release
+    when:
+      paths_changed: "src/**"
+    then:
+      ensure_changed:
+        - version.toml
+      require_clean:
+        - dist
+"#,
+        )
+        .unwrap();
+        let err = load_config(&config_path).unwrap_err();
+        assert!(err.to_string().contains("then.require_clean"));
+    }
+
+    #[test]
+    fn test_load_config_ensure_exists_exclusive() {
+        let temp = TempDir::new().unwrap();
+        let config_path = temp.path().join(CONFIG_FILENAME);
+        fs::write(
+            &config_path,
+            r#"
+checks:
+  - name: changelog
+    when:
+      paths_changed: "**/*.rs"
+    then:
+      ensure_exists: [CHANGELOG.md]
+      ensure_changed: [CHANGELOG.md]
+"#,
+        )
+        .unwrap();
+        let err = load_config(&config_path).unwrap_err();
+        assert!(err.to_string().contains("then.ensure_exists"));
+    }
+
+    #[test]
+    fn test_dependency_order() {
+        let checks = parse_checks(
+            r#"
+checks:
+  - name: clippy
+    depends_on: [test]
+    when:
+      paths_changed: "**/*.rs"
+    then:
+      ensure_commands: [cargo clippy]
+  - name: test
+    depends_on: [build]
+    when:
+      paths_changed: "**/*.rs"
+    then:
+      ensure_commands: [cargo test]
+  - name: build
+    when:
+      paths_changed: "**/*.rs"
+    then:
+      ensure_commands: [cargo build]
+"#,
+        );
+        assert_eq!(dependency_order(&checks).unwrap(), vec![2, 1, 0]);
+    }
+
+    #[test]
+    fn test_dependency_cycle_rejected() {
+        let temp = TempDir::new().unwrap();
+        let config_path = temp.path().join(CONFIG_FILENAME);
+        fs::write(
+            &config_path,
+            r#"
+checks:
+  - name: a
+    depends_on: [b]
+    when:
+      paths_changed: "**/*.rs"
+    then:
+      ensure_commands: [cargo test]
+  - name: b
+    depends_on: [a]
+    when:
+      paths_changed: "**/*.rs"
+    then:
+      ensure_commands: [cargo clippy]
+"#,
+        )
+        .unwrap();
+
+        let err = format!("{:#}", load_config(&config_path).unwrap_err());
+        assert!(err.contains("dependency cycle: a -> b -> a"), "{err}");
+    }
+
+    #[test]
+    fn test_unknown_dependency_rejected() {
+        let temp = TempDir::new().unwrap();
+        let config_path = temp.path().join(CONFIG_FILENAME);
+        fs::write(
+            &config_path,
+            r#"
+checks:
+  - name: clippy
+    depends_on: [tests]
+    when:
+      paths_changed: "**/*.rs"
+    then:
+      ensure_commands: [cargo clippy]
+"#,
+        )
+        .unwrap();
+
+        let err = load_config(&config_path).unwrap_err();
+        assert!(err.to_string().contains("unknown check 'tests'"));
+    }
+
+    #[test]
+    fn test_cwd_prefix_parsing() {
+        let checks = parse_checks(
+            r#"
+checks:
+  - name: foo-tests
+    when:
+      paths_changed: "**/*.ts"
+      cwd_prefix: packages/foo
+    then:
+      ensure_commands: [pnpm test]
+  - name: root-tests
+    when:
+      paths_changed: "**/*.rs"
+    then:
+      ensure_commands: [cargo test]
+"#,
+        );
+        assert_eq!(checks[0].when.cwd_prefix.as_deref(), Some("packages/foo"));
+        assert_eq!(checks[1].when.cwd_prefix, None);
+    }
+
+    #[test]
+    fn test_load_config_tool_mappings() {
+        let temp = TempDir::new().unwrap();
+        let config_path = temp.path().join(CONFIG_FILENAME);
+        fs::write(
+            &config_path,
+            r#"
+tool_mappings:
+  mcp__fs__write_file:
+    as: Write
+    key: path
+checks:
+  - name: tests
+    when:
+      paths_changed: "**/*.rs"
+    then:
+      ensure_commands: [cargo test]
+"#,
+        )
+        .unwrap();
+
+        let config = load_config(&config_path).unwrap();
+        let mapping = &config.tool_mappings["mcp__fs__write_file"];
+        assert_eq!(mapping.tool, BuiltinTool::Write);
+        assert_eq!(mapping.key(), "path");
+
+        // Only the built-in tools can be mapped to
+        fs::write(
+            &config_path,
+            r#"
+tool_mappings:
+  mcp__fs__read_file: {as: Read}
+checks:
+  - name: tests
+    when:
+      paths_changed: "**/*.rs"
+    then:
+      ensure_commands: [cargo test]
+"#,
+        )
+        .unwrap();
+        assert!(load_config(&config_path).is_err());
+    }
+
+    #[test]
+    fn test_tool_mapping_as_multi_edit() {
+        let mappings: HashMap<String, ToolMapping> =
+            serde_yaml::from_str("mcp__fs__edit_file: {as: MultiEdit, key: path}").unwrap();
+        let mapping = &mappings["mcp__fs__edit_file"];
+        assert_eq!(mapping.tool, BuiltinTool::MultiEdit);
+        assert_eq!(mapping.tool.name(), "MultiEdit");
+        assert_eq!(mapping.key(), "path");
+        assert_eq!(BuiltinTool::MultiEdit.default_key(), "file_path");
+    }
+
+    #[test]
+    fn test_on_parsing() {
+        let checks = parse_checks(
+            r#"
+checks:
+  - name: version
+    on: [stop, post_tool_use]
+    when:
+      paths_changed: "**/*.rs"
+    then:
+      ensure_changed: [version.toml]
+  - name: tests
+    when:
+      paths_changed: "**/*.rs"
+    then:
+      ensure_commands: [cargo test]
+"#,
+        );
+        assert_eq!(checks[0].on, vec![HookEvent::Stop, HookEvent::PostToolUse]);
+        assert_eq!(checks[1].on, vec![HookEvent::Stop]);
+    }
+
+    #[test]
+    fn test_post_tool_use_rejects_command_checks() {
+        let temp = TempDir::new().unwrap();
+        let config_path = temp.path().join(CONFIG_FILENAME);
+        for action in ["ensure_commands", "forbid_commands", "format_commands"] {
+            fs::write(
+                &config_path,
+                format!(
+                    r#"
+checks:
+  - name: tests
+    on: [stop, post_tool_use]
+    when:
+      paths_changed: "**/*.rs"
+    then:
+      {}: [cargo test]
+"#,
+                    action
+                ),
+            )
+            .unwrap();
+            let err = load_config(&config_path).unwrap_err().to_string();
+            assert!(err.contains(&format!("then.{}", action)), "{}", err);
+            assert!(err.contains("post_tool_use"), "{}", err);
+        }
+    }
+
+    #[test]
+    fn test_find_nearest_config() {
+        let temp = TempDir::new().unwrap();
+        let repo_root = temp.path();
+        let subdir = repo_root.join("src/lib");
+        fs::create_dir_all(&subdir).unwrap();
+
+        let config_path = repo_root.join(CONFIG_FILENAME);
+        fs::write(
+            &config_path,
+            r#"
+checks:
+  - name: test
+    when:
+      paths_changed: "**/*.rs"
+    then:
+      ensure_commands:
+        - cargo test
+"#,
+        )
+        .unwrap();
+
+        let loaded = find_nearest_config(&subdir, repo_root);
+        assert!(loaded.is_some());
+        assert_eq!(loaded.unwrap().config_dir, repo_root);
+    }
+
+    #[test]
+    fn test_find_nearest_config_nested() {
+        let temp = TempDir::new().unwrap();
+        let repo_root = temp.path();
+        let pkg_dir = repo_root.join("packages/foo");
+        let src_dir = pkg_dir.join("src");
+        fs::create_dir_all(&src_dir).unwrap();
+
+        // Config at package level, not repo root
+        let config_path = pkg_dir.join(CONFIG_FILENAME);
+        fs::write(
+            &config_path,
+            r#"
+checks:
+  - name: pkg-check
+    when:
+      paths_changed: "**/*.ts"
+    then:
+      ensure_commands:
+        - pnpm test
+"#,
+        )
+        .unwrap();
+
+        let loaded = find_nearest_config(&src_dir, repo_root);
+        assert!(loaded.is_some());
+        assert_eq!(loaded.unwrap().config_dir, pkg_dir);
+    }
+
+    const ROOT_CONFIG: &str = r#"
+checks:
+  - name: root-check
+    when:
+      paths_changed: "**/*"
+    then:
+      forbid_commands:
+        - git push --force
+  - name: shared
+    when:
+      paths_changed: "**/*.rs"
+    then:
+      ensure_commands:
+        - cargo test
+"#;
+
+    #[test]
+    fn test_find_nearest_config_inherit() {
+        let temp = TempDir::new().unwrap();
+        let repo_root = temp.path();
+        let pkg_dir = repo_root.join("packages/foo");
+        fs::create_dir_all(&pkg_dir).unwrap();
+
+        fs::write(repo_root.join(CONFIG_FILENAME), ROOT_CONFIG).unwrap();
+        fs::write(
+            pkg_dir.join(CONFIG_FILENAME),
+            r#"
+inherit: true
+checks:
+  - name: shared
+    when:
+      paths_changed: "**/*.rs"
+    then:
+      ensure_commands:
+        - cargo nextest run
+  - name: pkg-check
+    when:
+      paths_changed: "**/*.ts"
+    then:
+      ensure_commands:
+        - pnpm test
+"#,
+        )
+        .unwrap();
+
+        let loaded = find_nearest_config(&pkg_dir, repo_root).unwrap();
+        assert_eq!(loaded.config_dir, pkg_dir);
+        let names: Vec<&str> = loaded
+            .config
+            .checks
+            .iter()
+            .map(|c| c.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["root-check", "shared", "pkg-check"]);
+        assert_eq!(
+            loaded.config.checks[1].then.ensure_commands,
+            Some(vec!["cargo nextest run".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_find_nearest_config_no_inherit_replaces() {
+        let temp = TempDir::new().unwrap();
+        let repo_root = temp.path();
+        let pkg_dir = repo_root.join("packages/foo");
+        fs::create_dir_all(&pkg_dir).unwrap();
+
+        fs::write(repo_root.join(CONFIG_FILENAME), ROOT_CONFIG).unwrap();
+        fs::write(
+            pkg_dir.join(CONFIG_FILENAME),
+            r#"
+checks:
+  - name: pkg-check
+    when:
+      paths_changed: "**/*.ts"
+    then:
+      ensure_commands:
+        - pnpm test
+"#,
+        )
+        .unwrap();
+
+        let loaded = find_nearest_config(&pkg_dir, repo_root).unwrap();
+        assert_eq!(loaded.config.checks.len(), 1);
+        assert_eq!(loaded.config.checks[0].name, "pkg-check");
+    }
+
+    #[test]
+    fn test_find_nearest_config_none() {
+        let temp = TempDir::new().unwrap();
+        let repo_root = temp.path();
+        let subdir = repo_root.join("src");
+        fs::create_dir_all(&subdir).unwrap();
+
+        let global_path = repo_root.join("no-global.yaml");
+        let loaded = find_config_with_fallback(&subdir, repo_root, &global_path);
+        assert!(loaded.is_none());
+    }
+
+    #[test]
+    fn test_global_config_fallback() {
+        let temp = TempDir::new().unwrap();
+        let repo_root = temp.path().join("repo");
+        let subdir = repo_root.join("src");
+        fs::create_dir_all(&subdir).unwrap();
+        let global_path = temp.path().join("config.yaml");
+        fs::write(&global_path, ROOT_CONFIG).unwrap();
+
+        // No local config: the global one applies, relative to repo root
+        let loaded = find_config_with_fallback(&subdir, &repo_root, &global_path).unwrap();
+        assert_eq!(loaded.config_dir, repo_root);
+        assert_eq!(loaded.config.checks[0].name, "root-check");
+
+        // A local config takes precedence
+        fs::write(
+            subdir.join(CONFIG_FILENAME),
+            r#"
+checks:
+  - name: local
+    when:
+      paths_changed: "**/*.rs"
+    then:
+      ensure_commands:
+        - cargo test
+"#,
+        )
+        .unwrap();
+        let loaded = find_config_with_fallback(&subdir, &repo_root, &global_path).unwrap();
+        assert_eq!(loaded.config_dir, subdir);
+        let names: Vec<&str> = loaded
+            .config
+            .checks
+            .iter()
+            .map(|c| c.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["local"]);
     }
 }
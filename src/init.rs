@@ -0,0 +1,163 @@
+use crate::config::CONFIG_FILENAME;
+use anyhow::{bail, Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A project type recognised by a marker file, with its suggested preset
+/// and an example check
+struct ProjectType {
+    marker: &'static str,
+    preset: &'static str,
+    check: &'static str,
+}
+
+const PROJECT_TYPES: &[ProjectType] = &[
+    ProjectType {
+        marker: "Cargo.toml",
+        preset: "cargo",
+        check: r#"  # Tests must run after Rust sources change
+  - name: cargo-test
+    when:
+      paths_changed: "**/*.rs"
+    then:
+      ensure_commands:
+        - cargo test
+"#,
+    },
+    ProjectType {
+        marker: "package.json",
+        preset: "node",
+        check: r#"  # Tests must run after JavaScript/TypeScript sources change
+  - name: npm-test
+    when:
+      paths_changed:
+        - "**/*.ts"
+        - "**/*.js"
+    then:
+      ensure_commands:
+        - npm test
+"#,
+    },
+    ProjectType {
+        marker: "pyproject.toml",
+        preset: "python",
+        check: r#"  # Tests must run after Python sources change
+  - name: pytest
+    when:
+      paths_changed: "**/*.py"
+    then:
+      ensure_commands:
+        - pytest
+"#,
+    },
+];
+
+const GENERIC_CHECK: &str = r#"  # Example: require a command after editing matching files
+  - name: tests
+    when:
+      paths_changed: "src/**"
+    then:
+      ensure_commands:
+        - make test
+"#;
+
+/// Render a starter config for the project in `dir`
+pub fn render_config(dir: &Path) -> String {
+    let detected: Vec<&ProjectType> = PROJECT_TYPES
+        .iter()
+        .filter(|t| dir.join(t.marker).exists())
+        .collect();
+
+    let mut out = String::from(
+        "# rufio-hooks.yaml - checks rufio runs when Claude Code stops.\n\
+         #\n\
+         # Presets are loaded from $XDG_CONFIG_HOME/rufio/presets/<name>.yaml\n\
+         # (or define_presets in this file). Uncomment once they exist:\n\
+         # presets:\n",
+    );
+
+    let presets: Vec<&str> = if detected.is_empty() {
+        vec!["cargo"]
+    } else {
+        detected.iter().map(|t| t.preset).collect()
+    };
+    for preset in presets {
+        out.push_str(&format!("#   - {}\n", preset));
+    }
+
+    out.push_str("\nchecks:\n");
+    if detected.is_empty() {
+        out.push_str(GENERIC_CHECK);
+    } else {
+        for t in &detected {
+            out.push_str(t.check);
+        }
+    }
+
+    out
+}
+
+/// Write a starter config into `dir`, refusing to overwrite an existing one.
+/// Returns the path written.
+pub fn run(dir: &Path) -> Result<PathBuf> {
+    let path = dir.join(CONFIG_FILENAME);
+    if path.exists() {
+        bail!("{} already exists", path.display());
+    }
+
+    fs::write(&path, render_config(dir))
+        .with_context(|| format!("Failed to write config: {}", path.display()))?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::load_config;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_render_config_detects_cargo() {
+        let temp = TempDir::new().unwrap();
+        fs::write(temp.path().join("Cargo.toml"), "").unwrap();
+
+        let content = render_config(temp.path());
+        assert!(content.contains("#   - cargo"));
+        assert!(!content.contains("#   - node"));
+
+        let path = run(temp.path()).unwrap();
+        let config = load_config(&path).unwrap();
+        assert_eq!(config.checks.len(), 1);
+        assert_eq!(config.checks[0].name, "cargo-test");
+    }
+
+    #[test]
+    fn test_render_config_multiple_types() {
+        let temp = TempDir::new().unwrap();
+        fs::write(temp.path().join("package.json"), "{}").unwrap();
+        fs::write(temp.path().join("pyproject.toml"), "").unwrap();
+
+        let path = run(temp.path()).unwrap();
+        let config = load_config(&path).unwrap();
+        let names: Vec<&str> = config.checks.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, vec!["npm-test", "pytest"]);
+    }
+
+    #[test]
+    fn test_render_config_generic() {
+        let temp = TempDir::new().unwrap();
+        let path = run(temp.path()).unwrap();
+        let config = load_config(&path).unwrap();
+        assert_eq!(config.checks[0].name, "tests");
+    }
+
+    #[test]
+    fn test_run_refuses_overwrite() {
+        let temp = TempDir::new().unwrap();
+        fs::write(temp.path().join(CONFIG_FILENAME), "keep me").unwrap();
+
+        assert!(run(temp.path()).is_err());
+        let content = fs::read_to_string(temp.path().join(CONFIG_FILENAME)).unwrap();
+        assert_eq!(content, "keep me");
+    }
+}
@@ -1,18 +1,92 @@
+use crate::config::{BuiltinTool, ToolMapping};
+use crate::transcript::EDIT_TOOLS;
 use serde::Deserialize;
+use std::collections::HashMap;
 
 /// Input JSON from Claude Code hook system
 #[derive(Debug, Deserialize)]
 pub struct HookInput {
     pub hook_event_name: String,
     pub cwd: String,
-    #[allow(dead_code)]
     pub session_id: String,
     pub transcript_path: String,
     /// Tool name (only present for PreToolUse/PostToolUse events)
-    #[allow(dead_code)]
     pub tool_name: Option<String>,
+    /// Tool arguments (only present for PreToolUse/PostToolUse events)
+    pub tool_input: Option<serde_json::Value>,
+    /// Notification text (only present for Notification events)
+    pub message: Option<String>,
     /// True when Claude Code is re-invoking Stop after a previous block.
     /// Short-circuit to avoid a ping-pong loop when a check keeps failing.
     #[serde(default)]
     pub stop_hook_active: bool,
 }
+
+impl HookInput {
+    /// The file written by an Edit/Write/MultiEdit tool use, as given in the tool input.
+    /// Tools named in `tool_mappings` count as the built-in tool they map to.
+    pub fn edited_file(&self, tool_mappings: &HashMap<String, ToolMapping>) -> Option<&str> {
+        let name = self.tool_name.as_deref()?;
+        let key = match tool_mappings.get(name) {
+            Some(mapping) if mapping.tool == BuiltinTool::Bash => return None,
+            Some(mapping) => mapping.key(),
+            None if EDIT_TOOLS.contains(&name) => BuiltinTool::Edit.default_key(),
+            None => return None,
+        };
+        self.tool_input.as_ref()?.get(key)?.as_str()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn post_tool_use(tool: &str, input: serde_json::Value) -> HookInput {
+        HookInput {
+            hook_event_name: "PostToolUse".to_string(),
+            cwd: "/repo".to_string(),
+            session_id: "session".to_string(),
+            transcript_path: "/nonexistent".to_string(),
+            tool_name: Some(tool.to_string()),
+            tool_input: Some(input),
+            message: None,
+            stop_hook_active: false,
+        }
+    }
+
+    #[test]
+    fn test_edited_file_builtin_tools() {
+        let mappings = HashMap::new();
+        for tool in EDIT_TOOLS {
+            let input = post_tool_use(tool, serde_json::json!({"file_path": "src/main.rs"}));
+            assert_eq!(input.edited_file(&mappings), Some("src/main.rs"));
+        }
+        let input = post_tool_use("Bash", serde_json::json!({"command": "cargo test"}));
+        assert_eq!(input.edited_file(&mappings), None);
+    }
+
+    #[test]
+    fn test_edited_file_mapped_tool() {
+        let mappings: HashMap<String, ToolMapping> = serde_yaml::from_str(
+            r#"
+mcp__fs__write_file: {as: Write, key: path}
+mcp__fs__edit_file: {as: MultiEdit}
+mcp__shell__run: {as: Bash, key: path}
+"#,
+        )
+        .unwrap();
+
+        let input = post_tool_use("mcp__fs__write_file", serde_json::json!({"path": "a.rs"}));
+        assert_eq!(input.edited_file(&mappings), Some("a.rs"));
+        let input = post_tool_use(
+            "mcp__fs__edit_file",
+            serde_json::json!({"file_path": "b.rs"}),
+        );
+        assert_eq!(input.edited_file(&mappings), Some("b.rs"));
+        // Mapped to Bash, so not an edit even with a path-like key
+        let input = post_tool_use("mcp__shell__run", serde_json::json!({"path": "c.rs"}));
+        assert_eq!(input.edited_file(&mappings), None);
+        let input = post_tool_use("mcp__other__write", serde_json::json!({"path": "d.rs"}));
+        assert_eq!(input.edited_file(&mappings), None);
+    }
+}
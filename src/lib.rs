@@ -0,0 +1,1656 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::OpenOptions;
+use std::io::{Read, Write as IoWrite};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{Duration, Instant};
+use tracing::{debug, info, warn};
+
+pub mod checks;
+pub mod cli;
+pub mod config;
+pub mod init;
+pub mod input;
+pub mod notify;
+pub mod session;
+pub mod transcript;
+
+use checks::{CheckContext, CheckResult, Trigger};
+use cli::OutputFormat;
+use config::{
+    find_local_config, find_nearest_config, group_files_by_config, load_config_file, HookEvent,
+    Level, LoadedConfig, ReasonFormat,
+};
+use input::HookInput;
+use session::{LastBlock, SessionCache, StopSummary};
+
+/// Write to a log file when RUFIO_LOG is set, e.g. RUFIO_LOG=/tmp/rufio.log.
+pub fn log(msg: &str) {
+    let path = match std::env::var("RUFIO_LOG") {
+        Ok(p) if !p.is_empty() => p,
+        _ => return,
+    };
+    if let Ok(mut f) = OpenOptions::new().create(true).append(true).open(path) {
+        let _ = writeln!(f, "{}", msg);
+    }
+}
+
+/// Version line for `rufio version`, including the build commit when known.
+/// The version comes from version.toml, set by build.rs.
+pub fn version_string() -> String {
+    let version = env!("RUFIO_VERSION");
+    match option_env!("RUFIO_GIT_COMMIT") {
+        Some(commit) => format!("rufio {} ({})", version, commit),
+        None => format!("rufio {}", version),
+    }
+}
+
+/// A Stop hook decision telling Claude Code not to finish yet
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlockDecision {
+    /// Combined reasons from every blocking check
+    pub reason: String,
+}
+
+/// Handle one hook event, writing any hook output to `out`.
+/// Per-session state lives in `session_dir`, normally [`session::runtime_dir`].
+/// Returns the block decision when a Stop is blocked.
+pub fn handle_event(
+    input: HookInput,
+    session_dir: &Path,
+    out: &mut impl IoWrite,
+) -> Result<Option<BlockDecision>> {
+    log(&format!(
+        "hook_event={} cwd={} transcript={}",
+        input.hook_event_name, input.cwd, input.transcript_path
+    ));
+
+    info!(hook_event = %input.hook_event_name, cwd = %input.cwd);
+
+    match input.hook_event_name.as_str() {
+        "Stop" => {
+            if input.stop_hook_active {
+                log("stop_hook_active=true, skipping checks to avoid loop");
+            } else {
+                let ctx = EventContext::resolve(&input, session_dir, None);
+                return run_stop_checks(&ctx, out);
+            }
+        }
+        "PostToolUse" => run_post_tool_use_checks(&input, session_dir, out)?,
+        "PermissionRequest" => {
+            let ctx = EventContext::resolve(&input, session_dir, None);
+            notify_if_enabled(&ctx, "waiting for permission");
+        }
+        "Notification" => {
+            log(&format!("notification: {:?}", input.message));
+            let what = input.message.as_deref().unwrap_or("needs your input");
+            let ctx = EventContext::resolve(&input, session_dir, None);
+            notify_if_enabled(&ctx, what);
+        }
+        _ => log(&format!("ignoring event: {}", input.hook_event_name)),
+    }
+
+    Ok(None)
+}
+
+/// Evaluate the Stop checks for a hook input and report every result,
+/// without emitting a block decision.
+pub fn run_check_command(
+    input: &HookInput,
+    format: OutputFormat,
+    config: Option<PathBuf>,
+    stdout: &mut impl IoWrite,
+) -> Result<()> {
+    let session_dir = session::runtime_dir();
+    let ctx = EventContext::resolve(input, &session_dir, config);
+    let results = evaluate_stop_checks(&ctx, Deadline::none())?;
+
+    match format {
+        OutputFormat::Text => {
+            for result in &results {
+                let status = match (&result.reason, result.level) {
+                    (None, _) => "PASS",
+                    (Some(_), Level::Block) => "FAIL",
+                    (Some(_), Level::Warn) => "WARN",
+                };
+                match &result.reason {
+                    Some(reason) => {
+                        writeln!(stdout, "{} {}: {}", status, result.check_name, reason)?
+                    }
+                    None => writeln!(stdout, "{} {}", status, result.check_name)?,
+                }
+            }
+        }
+        OutputFormat::Json => {
+            writeln!(stdout, "{}", serde_json::to_string_pretty(&results)?)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Print which checks a change to `path` would trigger, and what they require.
+pub fn run_explain_command(path: &Path, stdout: &mut impl IoWrite) -> Result<()> {
+    let cwd = std::env::current_dir()?;
+    let file = cwd.join(path);
+    let repo_root = get_repo_root(&cwd.to_string_lossy()).unwrap_or_else(|| cwd.clone());
+    let file_dir = file.parent().unwrap_or(&file);
+
+    let loaded = match resolve_config_override(None, &cwd.to_string_lossy()) {
+        Some(config_path) => Some(load_config_file(&config_path, &repo_root)?),
+        None => find_nearest_config(file_dir, &repo_root),
+    };
+
+    let Some(loaded) = loaded else {
+        writeln!(stdout, "No config applies to {}", path.display())?;
+        return Ok(());
+    };
+
+    writeln!(stdout, "Config dir: {}", loaded.config_dir.display())?;
+    let file = file.to_string_lossy();
+    for check in &loaded.config.checks {
+        match checks::explain_file(
+            check,
+            &file,
+            check.config_dir(&loaded.config_dir),
+            &repo_root,
+        ) {
+            Trigger::Triggers => writeln!(
+                stdout,
+                "{}: triggers ({})",
+                check.name,
+                checks::describe_requirement(check)
+            )?,
+            Trigger::NoPathMatch => writeln!(
+                stdout,
+                "{}: not triggered (no match for {})",
+                check.name,
+                check.when.paths_changed.join(", ")
+            )?,
+            Trigger::PathMissing => writeln!(
+                stdout,
+                "{}: not triggered (path_exists {} not found)",
+                check.name,
+                check.when.path_exists.as_deref().unwrap_or_default()
+            )?,
+            Trigger::InvalidGlob(glob) => {
+                writeln!(stdout, "{}: invalid glob pattern '{}'", check.name, glob)?
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// An identical block this soon after the last one gets one re-evaluation,
+/// in case the transcript hadn't yet flushed the commands that fixed it
+const REBLOCK_GRACE_MS: u64 = 5_000;
+/// Pause before re-reading the transcript for a repeated block
+const REPARSE_DELAY: Duration = Duration::from_millis(300);
+
+fn run_stop_checks(ctx: &EventContext, out: &mut impl IoWrite) -> Result<Option<BlockDecision>> {
+    if is_disabled(ctx) {
+        log("rufio disabled here, skipping checks");
+        notify_if_enabled(ctx, "finished");
+        return Ok(None);
+    }
+
+    let input = ctx.input;
+    let deadline = Deadline::from_env();
+    let mut results = match evaluate_stop_checks(ctx, deadline) {
+        Err(e) if e.is::<DeadlineExceeded>() => return stop_timed_out(ctx, &e, out),
+        result => result?,
+    };
+    let (reason_format, write_results) = ctx
+        .cwd_config
+        .as_ref()
+        .map(|loaded| (loaded.config.reason_format, loaded.config.write_results))
+        .unwrap_or_default();
+
+    let session_dir = ctx.session_dir;
+    let previous = LastBlock::load(session_dir, &input.session_id);
+    let now = session::now_ms();
+    let first_output = stop_output(&results, reason_format);
+    let fallback = first_output.clone();
+    let output = recheck_repeated_block(first_output, previous.as_ref(), now, || {
+        log("same block as moments ago, re-reading transcript");
+        std::thread::sleep(REPARSE_DELAY);
+        match evaluate_stop_checks(ctx, deadline) {
+            Ok(rechecked) => {
+                results = rechecked;
+                Ok(stop_output(&results, reason_format))
+            }
+            // Out of time to recheck: the first evaluation still stands
+            Err(e) if e.is::<DeadlineExceeded>() => {
+                log(&format!("{}, keeping the first evaluation", e));
+                Ok(fallback)
+            }
+            Err(e) => Err(e),
+        }
+    })?;
+    match &output {
+        Some(output) => {
+            log(&format!("OUTPUT: {}", output));
+            writeln!(out, "{}", output)?;
+        }
+        None => log("all checks passed, nothing to report"),
+    }
+
+    let decision = output
+        .as_ref()
+        .filter(|output| output["decision"] == "block")
+        .and_then(|output| output["reason"].as_str())
+        .map(|reason| BlockDecision {
+            reason: reason.to_string(),
+        });
+    match &decision {
+        Some(decision) => LastBlock {
+            reason: decision.reason.clone(),
+            at_ms: now,
+        }
+        .store(session_dir, &input.session_id),
+        None => {
+            LastBlock::clear(session_dir, &input.session_id);
+            notify_if_enabled(ctx, "finished");
+        }
+    }
+    if write_results {
+        StopSummary {
+            at_ms: now,
+            blocked: decision.is_some(),
+            results: &results,
+        }
+        .store(session_dir, &input.session_id);
+    }
+
+    Ok(decision)
+}
+
+/// Give up on a Stop that ran out of time: never block, but say why nothing was checked
+fn stop_timed_out(
+    ctx: &EventContext,
+    error: &anyhow::Error,
+    out: &mut impl IoWrite,
+) -> Result<Option<BlockDecision>> {
+    warn!(error = %error, "stop checks timed out");
+    log(&format!("TIMEOUT: {}", error));
+    let output = serde_json::json!({
+        "systemMessage": format!("rufio skipped its checks: {}", error),
+    });
+    writeln!(out, "{}", output)?;
+    notify_if_enabled(ctx, "finished");
+    Ok(None)
+}
+
+/// Default time budget for a Stop evaluation, in milliseconds
+const DEFAULT_DEADLINE_MS: u64 = 5_000;
+
+/// When a Stop evaluation must give up, so the hook finishes its output
+/// before Claude Code's own timeout kills it
+#[derive(Debug, Clone, Copy)]
+struct Deadline {
+    /// None when there's no deadline
+    at: Option<Instant>,
+    budget: Duration,
+}
+
+/// A Stop evaluation ran past its deadline
+#[derive(Debug)]
+struct DeadlineExceeded {
+    budget: Duration,
+    step: &'static str,
+}
+
+impl std::fmt::Display for DeadlineExceeded {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "ran out of time ({}ms) before {}",
+            self.budget.as_millis(),
+            self.step
+        )
+    }
+}
+
+impl std::error::Error for DeadlineExceeded {}
+
+impl Deadline {
+    /// No deadline, e.g. for the interactive check subcommand
+    fn none() -> Self {
+        Deadline {
+            at: None,
+            budget: Duration::ZERO,
+        }
+    }
+
+    fn after(budget: Duration) -> Self {
+        Deadline {
+            at: Some(Instant::now() + budget),
+            budget,
+        }
+    }
+
+    /// From RUFIO_DEADLINE_MS (0 disables it), defaulting to DEFAULT_DEADLINE_MS
+    fn from_env() -> Self {
+        let budget_ms = std::env::var("RUFIO_DEADLINE_MS")
+            .ok()
+            .and_then(|ms| ms.trim().parse().ok())
+            .unwrap_or(DEFAULT_DEADLINE_MS);
+        match budget_ms {
+            0 => Deadline::none(),
+            ms => Deadline::after(Duration::from_millis(ms)),
+        }
+    }
+
+    /// Fails with DeadlineExceeded once the deadline has passed.
+    /// Called at step boundaries, naming the step about to start.
+    fn check(&self, step: &'static str) -> Result<()> {
+        match self.at {
+            Some(at) if Instant::now() >= at => Err(DeadlineExceeded {
+                budget: self.budget,
+                step,
+            }
+            .into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// If `output` would block with the same reason as a block sent within the
+/// grace period, evaluate again and go with that result instead.
+fn recheck_repeated_block(
+    output: Option<serde_json::Value>,
+    previous: Option<&LastBlock>,
+    now_ms: u64,
+    recheck: impl FnOnce() -> Result<Option<serde_json::Value>>,
+) -> Result<Option<serde_json::Value>> {
+    let reason = output
+        .as_ref()
+        .filter(|output| output["decision"] == "block")
+        .and_then(|output| output["reason"].as_str());
+    let repeated = match (reason, previous) {
+        (Some(reason), Some(previous)) => {
+            previous.reason == reason && now_ms.saturating_sub(previous.at_ms) <= REBLOCK_GRACE_MS
+        }
+        _ => false,
+    };
+    if repeated {
+        recheck()
+    } else {
+        Ok(output)
+    }
+}
+
+/// Run the checks marked `on: [post_tool_use]` against the file an Edit/Write/MultiEdit
+/// just touched, reporting failures as a non-blocking systemMessage.
+/// The transcript isn't read, so only the edited file counts as changed.
+fn run_post_tool_use_checks(
+    input: &HookInput,
+    session_dir: &Path,
+    out: &mut impl IoWrite,
+) -> Result<()> {
+    let ctx = EventContext::resolve(input, session_dir, None);
+    if is_disabled(&ctx) {
+        return Ok(());
+    }
+    // Tool mappings are session-wide, so they come from the cwd's config
+    let tool_mappings = ctx
+        .cwd_config
+        .as_ref()
+        .map(|loaded| &loaded.config.tool_mappings);
+    let Some(edited) = input.edited_file(tool_mappings.unwrap_or(&HashMap::new())) else {
+        return Ok(());
+    };
+    let roots = &ctx.roots;
+    let cwd_path = Path::new(&input.cwd);
+    let files_root = &files_root(roots, &input.cwd);
+    let Ok(file) = cwd_path
+        .join(edited)
+        .strip_prefix(files_root)
+        .map(Path::to_path_buf)
+    else {
+        log(&format!("edited file outside project: {}", edited));
+        return Ok(());
+    };
+    let changed_files = vec![file.to_string_lossy().to_string()];
+
+    let repo_root = roots
+        .git_root
+        .clone()
+        .unwrap_or_else(|| cwd_path.to_path_buf());
+    let groups: Vec<(LoadedConfig, Vec<String>)> = match &ctx.config_override {
+        Some(path) => vec![(load_config_file(path, &repo_root)?, changed_files)],
+        None => group_files_by_config(&changed_files, files_root, &repo_root),
+    };
+
+    // Checks are opt-in for this hook, so do no more work when none are tagged
+    let mut tagged = groups
+        .iter()
+        .flat_map(|(loaded, _)| &loaded.config.checks)
+        .filter(|check| check.on.contains(&HookEvent::PostToolUse))
+        .peekable();
+    if tagged.peek().is_none() {
+        return Ok(());
+    }
+    let needs_branch =
+        tagged.any(|check| check.when.branch.is_some() || check.when.skip_branches.is_some());
+
+    let branch = needs_branch.then(|| get_git_branch(&input.cwd)).flatten();
+    let context = CheckContext {
+        branch: branch.as_deref(),
+        hook: HookEvent::PostToolUse,
+        ..CheckContext::default()
+    };
+    let mut reasons = Vec::new();
+    let mut reason_format = ReasonFormat::default();
+    for (loaded, files) in &groups {
+        reason_format = loaded.config.reason_format;
+        let results = checks::run_checks(loaded, files, &[], files_root, &context);
+        reasons.extend(
+            results
+                .iter()
+                .filter_map(|result| result.reason.as_deref())
+                .map(sanitize_reason),
+        );
+    }
+
+    if !reasons.is_empty() {
+        let reasons: Vec<&str> = reasons.iter().map(String::as_str).collect();
+        let output = serde_json::json!({ "systemMessage": reason_format.join(&reasons) });
+        log(&format!("OUTPUT: {}", output));
+        writeln!(out, "{}", output)?;
+    }
+    Ok(())
+}
+
+/// One blocking failure in the Stop output's `rufio_reasons`, so tools reading
+/// the hook output can attribute it. Claude Code itself only reads `reason`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CheckReason {
+    pub check: String,
+    pub message: String,
+}
+
+/// Build the Stop hook output: a block decision for blocking failures, and a
+/// non-blocking systemMessage for warnings. None when there's nothing to say.
+fn stop_output(results: &[CheckResult], format: ReasonFormat) -> Option<serde_json::Value> {
+    let mut blocking = Vec::new();
+    let mut warnings = Vec::new();
+    for result in results {
+        if let Some(reason) = &result.reason {
+            let reason = sanitize_reason(reason);
+            if result.blocks() {
+                blocking.push(CheckReason {
+                    check: result.check_name.clone(),
+                    message: reason,
+                });
+            } else {
+                warnings.push(reason);
+            }
+        }
+    }
+
+    let mut output = serde_json::Map::new();
+    if !blocking.is_empty() {
+        let reasons: Vec<&str> = blocking.iter().map(|r| r.message.as_str()).collect();
+        output.insert("decision".to_string(), "block".into());
+        output.insert("reason".to_string(), format.join(&reasons).into());
+        output.insert(
+            "rufio_reasons".to_string(),
+            serde_json::to_value(&blocking).unwrap_or_default(),
+        );
+    }
+    if !warnings.is_empty() {
+        let warnings: Vec<&str> = warnings.iter().map(String::as_str).collect();
+        output.insert("systemMessage".to_string(), format.join(&warnings).into());
+    }
+
+    (!output.is_empty()).then_some(serde_json::Value::Object(output))
+}
+
+/// Strip ANSI escape sequences and control characters (other than newlines
+/// and tabs) that would render as garbage in Claude Code.
+fn sanitize_reason(reason: &str) -> String {
+    let mut clean = String::with_capacity(reason.len());
+    let mut chars = reason.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\u{1b}' => match chars.next() {
+                // CSI: parameters and intermediates up to a final byte in @..=~
+                Some('[') => {
+                    for c in chars.by_ref() {
+                        if ('@'..='~').contains(&c) {
+                            break;
+                        }
+                    }
+                }
+                // OSC: up to BEL or ST (ESC \)
+                Some(']') => {
+                    while let Some(c) = chars.next() {
+                        if c == '\u{7}' || (c == '\u{1b}' && chars.next_if_eq(&'\\').is_some()) {
+                            break;
+                        }
+                    }
+                }
+                // Any other escape is a single character
+                _ => {}
+            },
+            '\n' | '\t' => clean.push(c),
+            c if c.is_control() => {}
+            c => clean.push(c),
+        }
+    }
+    clean
+}
+
+/// Pick an explicit config file from the --config flag or RUFIO_CONFIG.
+/// Relative paths resolve against cwd; a missing file falls back to discovery.
+/// Like the global config, its checks are evaluated relative to the repo root.
+fn resolve_config_override(flag: Option<PathBuf>, cwd: &str) -> Option<PathBuf> {
+    let path = flag.or_else(|| {
+        std::env::var("RUFIO_CONFIG")
+            .ok()
+            .filter(|p| !p.is_empty())
+            .map(PathBuf::from)
+    })?;
+    let path = Path::new(cwd).join(path);
+
+    if path.is_file() {
+        log(&format!("config override={}", path.display()));
+        Some(path)
+    } else {
+        warn!(path = %path.display(), "config override not found, using discovery");
+        None
+    }
+}
+
+/// Run every applicable check for the changed files and return all results.
+/// With a config override, that config applies to all files instead of each
+/// file's nearest config.
+fn evaluate_stop_checks(ctx: &EventContext, deadline: Deadline) -> Result<Vec<CheckResult>> {
+    log("running stop checks");
+    let input = ctx.input;
+    let roots = &ctx.roots;
+    let git_root = roots.git_root.clone();
+    deadline.check("listing changed files")?;
+    let changed_files = get_changed_files(
+        &input.cwd,
+        git_root.as_deref(),
+        roots.submodule_root.as_deref(),
+    );
+    let cwd_path = Path::new(&input.cwd);
+    let files_root = &files_root(roots, &input.cwd);
+    // Tool mappings are session-wide, so they come from the cwd's config
+    let tool_mappings = ctx
+        .cwd_config
+        .as_ref()
+        .map(|loaded| loaded.config.tool_mappings.clone())
+        .unwrap_or_default();
+    deadline.check("reading the transcript")?;
+    let transcript = transcript::extract_tool_events(&input.transcript_path, &tool_mappings)?;
+    let mut events = transcript.events;
+    transcript::relativize_paths(&mut events, files_root);
+
+    log(&format!("changed_files={:?}", changed_files));
+    log(&format!(
+        "transcript_events={} current_turn={}",
+        events.len(),
+        transcript.current_turn
+    ));
+    for e in &events {
+        log(&format!(
+            "  event: tool={} cmd={:?} file={:?} idx={} turn={} at={:?}",
+            e.tool_name, e.command, e.file_path, e.index, e.turn, e.timestamp
+        ));
+    }
+
+    debug!(?changed_files);
+
+    let mut all_results = Vec::new();
+
+    let repo_root = git_root.clone().unwrap_or_else(|| cwd_path.to_path_buf());
+    let branch = get_git_branch(&input.cwd);
+    log(&format!("branch={:?}", branch));
+    let context = CheckContext {
+        branch: branch.as_deref(),
+        current_turn: transcript.current_turn,
+        hook: HookEvent::Stop,
+    };
+
+    // Group files by their nearest config and run each config's checks
+    let groups: Vec<(LoadedConfig, Vec<String>)> = match &ctx.config_override {
+        Some(path) => vec![(load_config_file(path, &repo_root)?, changed_files)],
+        None => group_files_by_config(&changed_files, files_root, &repo_root),
+    };
+
+    log(&format!("groups={}", groups.len()));
+    for (loaded, files) in &groups {
+        log(&format!(
+            "  group config_dir={} files={:?}",
+            loaded.config_dir.display(),
+            files
+        ));
+    }
+
+    debug!(groups = groups.len());
+
+    for (loaded, files) in &groups {
+        deadline.check("running checks")?;
+        let results = checks::run_checks(loaded, files, &events, files_root, &context);
+
+        for result in results {
+            log(&format!(
+                "  check={} reason={:?}",
+                result.check_name, result.reason
+            ));
+            all_results.push(result);
+        }
+    }
+
+    Ok(all_results)
+}
+
+/// What a hook event needs to know about where it runs, resolved once per event
+struct EventContext<'a> {
+    input: &'a HookInput,
+    /// Where per-session state lives
+    session_dir: &'a Path,
+    roots: GitRoots,
+    /// An explicit config replacing discovery, from --config or RUFIO_CONFIG
+    config_override: Option<PathBuf>,
+    /// The config that applies to cwd, for session-wide settings such as
+    /// disabled, notifications, reason_format and tool_mappings
+    cwd_config: Option<LoadedConfig>,
+}
+
+impl<'a> EventContext<'a> {
+    fn resolve(input: &'a HookInput, session_dir: &'a Path, config_flag: Option<PathBuf>) -> Self {
+        let cwd_path = Path::new(&input.cwd);
+        let roots = resolve_git_roots(input, session_dir);
+        let config_override = resolve_config_override(config_flag, &input.cwd);
+        let repo_root = roots.git_root.as_deref().unwrap_or(cwd_path);
+        let cwd_config = match &config_override {
+            Some(path) => load_config_file(path, repo_root).ok(),
+            None => find_nearest_config(cwd_path, repo_root),
+        };
+
+        EventContext {
+            input,
+            session_dir,
+            roots,
+            config_override,
+            cwd_config,
+        }
+    }
+}
+
+/// Marker file that turns rufio off for the directory holding it and below
+const IGNORE_FILE: &str = ".rufioignore";
+
+/// Whether rufio is turned off for cwd, by a `.rufioignore` at or above cwd
+/// (within the repo) or by `disabled: true` in the cwd's config.
+fn is_disabled(ctx: &EventContext) -> bool {
+    let cwd_path = Path::new(&ctx.input.cwd);
+    let repo_root = ctx.roots.git_root.as_deref().unwrap_or(cwd_path);
+    has_ignore_file(cwd_path, repo_root)
+        || ctx
+            .cwd_config
+            .as_ref()
+            .is_some_and(|loaded| loaded.config.disabled)
+}
+
+fn has_ignore_file(cwd: &Path, repo_root: &Path) -> bool {
+    for dir in cwd.ancestors() {
+        if dir.join(IGNORE_FILE).exists() {
+            return true;
+        }
+        if dir == repo_root {
+            break;
+        }
+    }
+    false
+}
+
+/// The directory changed files are relative to, matching get_changed_files:
+/// the project root holding cwd, else the repository root, else cwd. In a
+/// submodule whose config lives in the superproject, it's the superproject.
+fn files_root(roots: &GitRoots, cwd: &str) -> PathBuf {
+    match (&roots.submodule_root, &roots.git_root) {
+        (Some(_), Some(superproject)) => superproject.clone(),
+        (None, Some(git_root)) => {
+            find_project_root(cwd, git_root).unwrap_or_else(|| git_root.clone())
+        }
+        _ => PathBuf::from(cwd),
+    }
+}
+
+/// Send a desktop notification if the config nearest to cwd opts in.
+fn notify_if_enabled(ctx: &EventContext, what: &str) {
+    let enabled = ctx
+        .cwd_config
+        .as_ref()
+        .is_some_and(|loaded| loaded.config.notifications);
+    if !enabled {
+        return;
+    }
+
+    let project_name = derive_project_name(&ctx.input.cwd, ctx.roots.git_root.as_deref());
+    let message = notify::format_message(&project_name, what);
+    log(&format!("notify: {}", message));
+    notify::send(&message);
+}
+
+/// Derive a human-friendly project name from the project (or git) root of cwd.
+fn derive_project_name(cwd: &str, git_root: Option<&Path>) -> String {
+    let root = match git_root {
+        Some(git_root) => {
+            find_project_root(cwd, git_root).unwrap_or_else(|| git_root.to_path_buf())
+        }
+        None => PathBuf::from(cwd),
+    };
+
+    root.file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| cwd.to_string())
+}
+
+/// Which view of the repository counts as "changed", set via RUFIO_DIFF_MODE.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DiffMode {
+    /// Staged, unstaged and untracked files (default)
+    WorkingTree,
+    /// Only files staged for commit
+    Staged,
+    /// Tracked files that differ from HEAD
+    Head,
+}
+
+impl DiffMode {
+    fn from_env() -> Self {
+        match std::env::var("RUFIO_DIFF_MODE").as_deref() {
+            Ok("staged") => DiffMode::Staged,
+            Ok("head") => DiffMode::Head,
+            Ok("working-tree") | Ok("") | Err(_) => DiffMode::WorkingTree,
+            Ok(other) => {
+                warn!(mode = other, "unknown RUFIO_DIFF_MODE, using working-tree");
+                DiffMode::WorkingTree
+            }
+        }
+    }
+
+    fn git_args(self) -> &'static [&'static str] {
+        match self {
+            DiffMode::WorkingTree => &["status", "--porcelain", "-uall"],
+            DiffMode::Staged => &["diff", "--cached", "--name-only"],
+            DiffMode::Head => &["diff", "--name-only", "HEAD"],
+        }
+    }
+}
+
+fn get_changed_files(
+    cwd: &str,
+    git_root: Option<&Path>,
+    submodule_root: Option<&Path>,
+) -> Vec<String> {
+    let mode = DiffMode::from_env();
+
+    if let (Some(superproject), Some(submodule)) = (git_root, submodule_root) {
+        if let Ok(prefix) = submodule.strip_prefix(superproject) {
+            // The config lives in the superproject, so keep every path
+            // relative to it rather than narrowing to a project under cwd
+            return merge_submodule_files(
+                git_changed_files(superproject, mode),
+                &prefix.to_string_lossy(),
+                git_changed_files(submodule, mode),
+            );
+        }
+    }
+
+    let all_files = git_changed_files(Path::new(cwd), mode);
+    filter_to_project(cwd, git_root, all_files)
+}
+
+/// Changed files reported by git in `dir`, relative to its repository root.
+fn git_changed_files(dir: &Path, mode: DiffMode) -> Vec<String> {
+    let output = match Command::new("git")
+        .args(mode.git_args())
+        .current_dir(dir)
+        .output()
+    {
+        Ok(o) => o,
+        Err(_) => return Vec::new(),
+    };
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    parse_changed_files(mode, &stdout)
+}
+
+/// Replace the superproject's entry for a submodule with the files changed
+/// inside it, prefixed with the submodule's path.
+fn merge_submodule_files(
+    superproject_files: Vec<String>,
+    prefix: &str,
+    submodule_files: Vec<String>,
+) -> Vec<String> {
+    let prefix_with_slash = format!("{}/", prefix);
+    superproject_files
+        .into_iter()
+        .filter(|f| f != prefix && !f.starts_with(&prefix_with_slash))
+        .chain(
+            submodule_files
+                .into_iter()
+                .map(|f| format!("{}{}", prefix_with_slash, f)),
+        )
+        .collect()
+}
+
+/// Parse git output for the given mode into repo-relative file paths.
+fn parse_changed_files(mode: DiffMode, stdout: &str) -> Vec<String> {
+    match mode {
+        DiffMode::WorkingTree => stdout.lines().filter_map(parse_porcelain_line).collect(),
+        DiffMode::Staged | DiffMode::Head => stdout
+            .lines()
+            .filter(|line| !line.is_empty())
+            .filter_map(|line| {
+                if line.starts_with('"') {
+                    unquote_c_style(line).map(|(unquoted, _)| unquoted)
+                } else {
+                    Some(line.to_string())
+                }
+            })
+            .collect(),
+    }
+}
+
+/// Parse one `git status --porcelain` line into the path it refers to.
+/// Renames and copies yield the destination path; C-quoted paths are unquoted.
+pub(crate) fn parse_porcelain_line(line: &str) -> Option<String> {
+    let status = line.get(..2)?;
+    let mut path = line.get(3..)?;
+
+    if status.contains(['R', 'C']) {
+        // "old -> new": skip past the source path
+        path = if path.starts_with('"') {
+            let (_, after) = unquote_c_style(path)?;
+            after.strip_prefix(" -> ")?
+        } else {
+            path.split_once(" -> ").map(|(_, to)| to)?
+        };
+    }
+
+    if path.starts_with('"') {
+        unquote_c_style(path).map(|(unquoted, _)| unquoted)
+    } else {
+        Some(path.to_string())
+    }
+}
+
+/// Decode a leading C-style quoted string as emitted by git for unusual paths.
+/// Returns the decoded string and the remainder after the closing quote.
+fn unquote_c_style(s: &str) -> Option<(String, &str)> {
+    let bytes = s.as_bytes();
+    if bytes.first() != Some(&b'"') {
+        return None;
+    }
+
+    let mut out: Vec<u8> = Vec::new();
+    let mut i = 1;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'"' => {
+                let decoded = String::from_utf8_lossy(&out).to_string();
+                return Some((decoded, &s[i + 1..]));
+            }
+            b'\\' => {
+                let escaped = *bytes.get(i + 1)?;
+                i += 2;
+                match escaped {
+                    b'a' => out.push(0x07),
+                    b'b' => out.push(0x08),
+                    b'f' => out.push(0x0c),
+                    b'n' => out.push(b'\n'),
+                    b'r' => out.push(b'\r'),
+                    b't' => out.push(b'\t'),
+                    b'v' => out.push(0x0b),
+                    b'0'..=b'7' => {
+                        // Three-digit octal escape for a raw byte
+                        let digits = s.get(i - 1..i + 2)?;
+                        out.push(u8::from_str_radix(digits, 8).ok()?);
+                        i += 2;
+                    }
+                    other => out.push(other),
+                }
+            }
+            b => {
+                out.push(b);
+                i += 1;
+            }
+        }
+    }
+
+    None
+}
+
+/// Filter files to only those within the project boundary.
+/// Returns files with the project prefix stripped if applicable.
+fn filter_to_project(cwd: &str, git_root: Option<&Path>, files: Vec<String>) -> Vec<String> {
+    let git_root = match git_root {
+        Some(root) => root,
+        None => return files,
+    };
+
+    let project_root = match find_project_root(cwd, git_root) {
+        Some(root) => root,
+        None => return files,
+    };
+
+    strip_project_prefix(files, git_root, &project_root)
+}
+
+/// Strip the project prefix from git-root-relative file paths.
+/// When project_root == git_root, returns files unchanged.
+/// Otherwise filters to files under the project and strips the prefix.
+fn strip_project_prefix(files: Vec<String>, git_root: &Path, project_root: &Path) -> Vec<String> {
+    if project_root == git_root {
+        return files;
+    }
+
+    let prefix = match project_root.strip_prefix(git_root) {
+        Ok(p) => p.to_string_lossy().to_string(),
+        Err(_) => return files,
+    };
+
+    let prefix_with_slash = format!("{}/", prefix);
+
+    files
+        .into_iter()
+        .filter(|f| f.starts_with(&prefix_with_slash))
+        .map(|f| f[prefix_with_slash.len()..].to_string())
+        .collect()
+}
+
+/// Find the project root by walking up from cwd looking for marker files.
+/// Stops at git_root. Returns None if no marker found.
+fn find_project_root(cwd: &str, git_root: &Path) -> Option<PathBuf> {
+    let mut current = PathBuf::from(cwd);
+
+    loop {
+        if current.join("shell.nix").exists() || current.join("CLAUDE.md").exists() {
+            return Some(current);
+        }
+
+        if current == git_root {
+            return None;
+        }
+
+        if !current.pop() {
+            return None;
+        }
+    }
+}
+
+/// The repository roots an event's changed files come from
+#[derive(Debug, Default)]
+struct GitRoots {
+    /// Repository root, None outside any repository. The superproject's root
+    /// when cwd is in a submodule whose config lives there.
+    git_root: Option<PathBuf>,
+    /// Set when cwd is inside a submodule but the config lives in the superproject
+    submodule_root: Option<PathBuf>,
+}
+
+/// Get the git roots for the session's cwd. The git lookups are cached per
+/// session; whether a submodule defers to its superproject's config depends
+/// on which config files exist, so that's decided afresh every event.
+fn resolve_git_roots(input: &HookInput, session_dir: &Path) -> GitRoots {
+    let cache =
+        SessionCache::load(session_dir, &input.session_id, &input.cwd).unwrap_or_else(|| {
+            let git_root = get_repo_root(&input.cwd);
+            let superproject_root = git_root
+                .as_ref()
+                .and_then(|_| get_superproject_root(&input.cwd));
+            let cache = SessionCache {
+                cwd: input.cwd.clone(),
+                git_root,
+                superproject_root,
+            };
+            cache.store(session_dir, &input.session_id);
+            cache
+        });
+
+    match (cache.git_root, cache.superproject_root) {
+        (Some(toplevel), Some(superproject))
+            if config_lives_in_superproject(Path::new(&input.cwd), &toplevel, &superproject) =>
+        {
+            log(&format!(
+                "submodule {} uses superproject {}",
+                toplevel.display(),
+                superproject.display()
+            ));
+            GitRoots {
+                git_root: Some(superproject),
+                submodule_root: Some(toplevel),
+            }
+        }
+        (toplevel, _) => GitRoots {
+            git_root: toplevel,
+            submodule_root: None,
+        },
+    }
+}
+
+/// True when no config exists between cwd and the submodule root, but one
+/// exists above it in the superproject.
+fn config_lives_in_superproject(cwd: &Path, submodule: &Path, superproject: &Path) -> bool {
+    if find_local_config(cwd, submodule).is_some() {
+        return false;
+    }
+    submodule
+        .parent()
+        .is_some_and(|parent| find_local_config(parent, superproject).is_some())
+}
+
+/// Get the working tree of the superproject when cwd is inside a submodule.
+fn get_superproject_root(cwd: &str) -> Option<PathBuf> {
+    let output = Command::new("git")
+        .args(["rev-parse", "--show-superproject-working-tree"])
+        .current_dir(cwd)
+        .output()
+        .ok()?;
+
+    let path = String::from_utf8_lossy(&output.stdout);
+    let path = path.trim();
+    (output.status.success() && !path.is_empty()).then(|| PathBuf::from(path))
+}
+
+/// The git toplevel, or outside git the RUFIO_REPO_ROOT override or the
+/// nearest ancestor holding a `.jj`/`.hg` directory.
+fn get_repo_root(cwd: &str) -> Option<PathBuf> {
+    get_git_root(cwd).or_else(|| {
+        let env_root = std::env::var_os("RUFIO_REPO_ROOT").map(PathBuf::from);
+        fallback_repo_root(Path::new(cwd), env_root)
+    })
+}
+
+/// The repo root outside git: `env_root` (from RUFIO_REPO_ROOT) when set,
+/// else the nearest ancestor of `cwd` holding a `.jj`/`.hg` directory.
+fn fallback_repo_root(cwd: &Path, env_root: Option<PathBuf>) -> Option<PathBuf> {
+    if let Some(root) = env_root.filter(|root| !root.as_os_str().is_empty()) {
+        return Some(root);
+    }
+    cwd.ancestors()
+        .find(|dir| dir.join(".jj").is_dir() || dir.join(".hg").is_dir())
+        .map(Path::to_path_buf)
+}
+
+/// Get the git repository root directory.
+fn get_git_root(cwd: &str) -> Option<PathBuf> {
+    let output = Command::new("git")
+        .args(["rev-parse", "--show-toplevel"])
+        .current_dir(cwd)
+        .output()
+        .ok()?;
+
+    if output.status.success() {
+        let path = String::from_utf8_lossy(&output.stdout);
+        Some(PathBuf::from(path.trim()))
+    } else {
+        None
+    }
+}
+
+/// Get the current git branch name ("HEAD" when detached).
+fn get_git_branch(cwd: &str) -> Option<String> {
+    let output = Command::new("git")
+        .args(["rev-parse", "--abbrev-ref", "HEAD"])
+        .current_dir(cwd)
+        .output()
+        .ok()?;
+
+    if output.status.success() {
+        let branch = String::from_utf8_lossy(&output.stdout);
+        Some(branch.trim().to_string())
+    } else {
+        None
+    }
+}
+
+/// Read and parse a hook input JSON document
+pub fn read_input(mut reader: impl Read) -> Result<HookInput> {
+    let mut buffer = String::new();
+    reader.read_to_string(&mut buffer)?;
+    let input: HookInput = serde_json::from_str(&buffer)?;
+    Ok(input)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_files_root_matches_changed_files() {
+        let temp = TempDir::new().unwrap();
+        let repo = temp.path().to_path_buf();
+        let nested = repo.join("src/nested");
+        let project = repo.join("projects/foo");
+        fs::create_dir_all(&nested).unwrap();
+        fs::create_dir_all(project.join("src")).unwrap();
+        fs::write(project.join("shell.nix"), "").unwrap();
+        let in_repo = GitRoots {
+            git_root: Some(repo.clone()),
+            submodule_root: None,
+        };
+
+        // Without a project marker, git's paths are relative to the repo root
+        assert_eq!(files_root(&in_repo, &nested.to_string_lossy()), repo);
+        // Inside a project they're narrowed to the project root
+        let cwd = project.join("src");
+        assert_eq!(files_root(&in_repo, &cwd.to_string_lossy()), project);
+        // A submodule deferring to its superproject uses the superproject
+        let in_submodule = GitRoots {
+            git_root: Some(repo.clone()),
+            submodule_root: Some(project.clone()),
+        };
+        assert_eq!(files_root(&in_submodule, &cwd.to_string_lossy()), repo);
+        // Outside git there's only cwd
+        let cwd = nested.to_string_lossy();
+        assert_eq!(files_root(&GitRoots::default(), &cwd), nested);
+    }
+
+    #[test]
+    fn test_find_project_root_with_shell_nix() {
+        let temp = TempDir::new().unwrap();
+        let git_root = temp.path();
+        let subproject = git_root.join("projects/foo");
+        fs::create_dir_all(&subproject).unwrap();
+        fs::write(subproject.join("shell.nix"), "").unwrap();
+
+        let result = find_project_root(subproject.to_str().unwrap(), git_root);
+        assert_eq!(result, Some(subproject));
+    }
+
+    #[test]
+    fn test_find_project_root_with_claude_md() {
+        let temp = TempDir::new().unwrap();
+        let git_root = temp.path();
+        let subproject = git_root.join("projects/bar");
+        fs::create_dir_all(&subproject).unwrap();
+        fs::write(subproject.join("CLAUDE.md"), "").unwrap();
+
+        let result = find_project_root(subproject.to_str().unwrap(), git_root);
+        assert_eq!(result, Some(subproject));
+    }
+
+    #[test]
+    fn test_find_project_root_walks_up() {
+        let temp = TempDir::new().unwrap();
+        let git_root = temp.path();
+        let subproject = git_root.join("projects/baz");
+        let deep_dir = subproject.join("src/lib");
+        fs::create_dir_all(&deep_dir).unwrap();
+        fs::write(subproject.join("shell.nix"), "").unwrap();
+
+        let result = find_project_root(deep_dir.to_str().unwrap(), git_root);
+        assert_eq!(result, Some(subproject));
+    }
+
+    #[test]
+    fn test_repo_root_from_jj_marker() {
+        let temp = TempDir::new().unwrap();
+        let root = temp.path().join("repo");
+        let deep_dir = root.join("crates/core/src");
+        fs::create_dir_all(&deep_dir).unwrap();
+        fs::create_dir_all(root.join(".jj")).unwrap();
+
+        let result = fallback_repo_root(&deep_dir, None);
+        assert_eq!(result, Some(root.clone()));
+
+        // Files are then filtered to the subproject within that root
+        fs::write(root.join("crates/core/shell.nix"), "").unwrap();
+        let files = vec![
+            "crates/core/src/lib.rs".to_string(),
+            "README.md".to_string(),
+        ];
+        let filtered = filter_to_project(deep_dir.to_str().unwrap(), Some(&root), files);
+        assert_eq!(filtered, vec!["src/lib.rs".to_string()]);
+    }
+
+    #[test]
+    fn test_repo_root_env_override() {
+        let temp = TempDir::new().unwrap();
+        let root = temp.path().join("plain");
+        let subdir = root.join("sub");
+        fs::create_dir_all(&subdir).unwrap();
+
+        // Nothing inside temp marks a root; what's above it isn't ours to assert
+        let found = fallback_repo_root(&subdir, None);
+        assert!(
+            !found.as_deref().is_some_and(|r| r.starts_with(temp.path())),
+            "{found:?}"
+        );
+        assert_eq!(
+            fallback_repo_root(&subdir, Some(root.clone())),
+            Some(root.clone())
+        );
+
+        // The override wins over markers further up
+        fs::create_dir_all(temp.path().join(".hg")).unwrap();
+        assert_eq!(
+            fallback_repo_root(&subdir, None),
+            Some(temp.path().to_path_buf())
+        );
+        assert_eq!(fallback_repo_root(&subdir, Some(root.clone())), Some(root));
+    }
+
+    #[test]
+    fn test_find_project_root_no_marker_returns_none() {
+        let temp = TempDir::new().unwrap();
+        let git_root = temp.path();
+        let subdir = git_root.join("some/path");
+        fs::create_dir_all(&subdir).unwrap();
+
+        let result = find_project_root(subdir.to_str().unwrap(), git_root);
+        assert_eq!(result, None);
+    }
+
+    #[test]
+    fn test_find_project_root_marker_at_git_root() {
+        let temp = TempDir::new().unwrap();
+        let git_root = temp.path();
+        fs::write(git_root.join("CLAUDE.md"), "").unwrap();
+
+        let result = find_project_root(git_root.to_str().unwrap(), git_root);
+        assert_eq!(result, Some(git_root.to_path_buf()));
+    }
+
+    #[test]
+    fn test_strip_project_prefix_in_monorepo() {
+        let git_root = PathBuf::from("/repo");
+        let project_root = PathBuf::from("/repo/projects/foo");
+
+        let files = vec![
+            "projects/foo/src/main.rs".to_string(),
+            "projects/foo/src/lib.rs".to_string(),
+            "projects/bar/other.rs".to_string(),
+        ];
+
+        let result = strip_project_prefix(files, &git_root, &project_root);
+
+        assert_eq!(result, vec!["src/main.rs", "src/lib.rs"]);
+    }
+
+    #[test]
+    fn test_strip_project_prefix_no_strip_when_at_git_root() {
+        let git_root = PathBuf::from("/repo");
+        let project_root = PathBuf::from("/repo");
+
+        let files = vec!["src/main.rs".to_string(), "src/lib.rs".to_string()];
+
+        let result = strip_project_prefix(files, &git_root, &project_root);
+
+        assert_eq!(result, vec!["src/main.rs", "src/lib.rs"]);
+    }
+
+    #[test]
+    fn test_parse_changed_files_working_tree() {
+        let stdout = " M src/main.rs\nA  src/new.rs\n?? notes.txt\n";
+        let result = parse_changed_files(DiffMode::WorkingTree, stdout);
+        assert_eq!(result, vec!["src/main.rs", "src/new.rs", "notes.txt"]);
+    }
+
+    #[test]
+    fn test_parse_porcelain_line_modification() {
+        assert_eq!(
+            parse_porcelain_line(" M src/main.rs"),
+            Some("src/main.rs".to_string())
+        );
+    }
+
+    #[test]
+    fn test_parse_porcelain_line_rename() {
+        assert_eq!(
+            parse_porcelain_line("R  src/old.rs -> src/new.rs"),
+            Some("src/new.rs".to_string())
+        );
+        assert_eq!(
+            parse_porcelain_line(r#"R  "old name.rs" -> "new name.rs""#),
+            Some("new name.rs".to_string())
+        );
+    }
+
+    #[test]
+    fn test_parse_porcelain_line_quoted() {
+        assert_eq!(
+            parse_porcelain_line(r#"?? "docs/my notes.md""#),
+            Some("docs/my notes.md".to_string())
+        );
+        assert_eq!(
+            parse_porcelain_line(r#" M "caf\303\251\t\"x\".rs""#),
+            Some("café\t\"x\".rs".to_string())
+        );
+    }
+
+    #[test]
+    fn test_parse_changed_files_staged() {
+        let stdout = "src/main.rs\nsrc/new.rs\n";
+        let result = parse_changed_files(DiffMode::Staged, stdout);
+        assert_eq!(result, vec!["src/main.rs", "src/new.rs"]);
+    }
+
+    #[test]
+    fn test_parse_changed_files_head() {
+        let stdout = "Cargo.toml\n\nsrc/lib.rs\n";
+        let result = parse_changed_files(DiffMode::Head, stdout);
+        assert_eq!(result, vec!["Cargo.toml", "src/lib.rs"]);
+    }
+
+    #[test]
+    fn test_parse_changed_files_staged_quoted() {
+        let stdout = "\"src/caf\\303\\251.rs\"\n\"with\\ttab.rs\"\nplain.rs\n";
+        let result = parse_changed_files(DiffMode::Staged, stdout);
+        assert_eq!(result, vec!["src/café.rs", "with\ttab.rs", "plain.rs"]);
+    }
+
+    #[test]
+    fn test_merge_submodule_files() {
+        let superproject_files = vec![
+            "README.md".to_string(),
+            "vendor/lib".to_string(),
+            "vendor/library.txt".to_string(),
+        ];
+        let submodule_files = vec!["src/lib.rs".to_string()];
+
+        let result = merge_submodule_files(superproject_files, "vendor/lib", submodule_files);
+        assert_eq!(
+            result,
+            vec!["README.md", "vendor/library.txt", "vendor/lib/src/lib.rs"]
+        );
+    }
+
+    #[test]
+    fn test_config_lives_in_superproject() {
+        let temp = TempDir::new().unwrap();
+        let superproject = temp.path();
+        let submodule = superproject.join("vendor/lib");
+        let cwd = submodule.join("src");
+        fs::create_dir_all(&cwd).unwrap();
+        let config = "checks:\n  - name: test\n    when:\n      paths_changed: \"**/*.rs\"\n    then:\n      ensure_commands:\n        - cargo test\n";
+
+        // No config anywhere
+        assert!(!config_lives_in_superproject(
+            &cwd,
+            &submodule,
+            superproject
+        ));
+
+        // Config only at the outer root
+        fs::write(superproject.join(config::CONFIG_FILENAME), config).unwrap();
+        assert!(config_lives_in_superproject(&cwd, &submodule, superproject));
+
+        // The submodule's own config wins
+        fs::write(submodule.join(config::CONFIG_FILENAME), config).unwrap();
+        assert!(!config_lives_in_superproject(
+            &cwd,
+            &submodule,
+            superproject
+        ));
+    }
+
+    #[test]
+    fn test_resolve_git_roots_rechecks_submodule_config() {
+        let temp = TempDir::new().unwrap();
+        let session_dir = TempDir::new().unwrap();
+        let superproject = temp.path().to_path_buf();
+        let submodule = superproject.join("vendor/lib");
+        fs::create_dir_all(&submodule).unwrap();
+        let config = "checks:\n  - name: test\n    when:\n      paths_changed: \"**/*.rs\"\n    then:\n      ensure_commands:\n        - cargo test\n";
+        fs::write(superproject.join(config::CONFIG_FILENAME), config).unwrap();
+
+        let cwd = submodule.to_string_lossy().to_string();
+        SessionCache {
+            cwd: cwd.clone(),
+            git_root: Some(submodule.clone()),
+            superproject_root: Some(superproject.clone()),
+        }
+        .store(session_dir.path(), "s");
+        let input = read_input(
+            serde_json::json!({
+                "hook_event_name": "Stop",
+                "cwd": cwd,
+                "session_id": "s",
+                "transcript_path": "/nonexistent",
+            })
+            .to_string()
+            .as_bytes(),
+        )
+        .unwrap();
+
+        let roots = resolve_git_roots(&input, session_dir.path());
+        assert_eq!(roots.git_root, Some(superproject));
+        assert_eq!(roots.submodule_root, Some(submodule.clone()));
+
+        // Adding a config to the submodule takes effect within the session
+        fs::write(submodule.join(config::CONFIG_FILENAME), config).unwrap();
+        let roots = resolve_git_roots(&input, session_dir.path());
+        assert_eq!(roots.git_root, Some(submodule));
+        assert_eq!(roots.submodule_root, None);
+    }
+
+    fn result(name: &str, reason: Option<&str>, level: Level) -> CheckResult {
+        CheckResult {
+            check_name: name.to_string(),
+            reason: reason.map(String::from),
+            level,
+        }
+    }
+
+    #[test]
+    fn test_stop_output_silent_when_passing() {
+        let results = vec![result("tests", None, Level::Block)];
+        assert_eq!(stop_output(&results, ReasonFormat::Inline), None);
+    }
+
+    #[test]
+    fn test_stop_output_system_message_for_warnings() {
+        let results = vec![
+            result("tests", None, Level::Block),
+            result(
+                "changelog",
+                Some("consider a \"CHANGELOG\" entry"),
+                Level::Warn,
+            ),
+        ];
+        assert_eq!(
+            stop_output(&results, ReasonFormat::Inline),
+            Some(serde_json::json!({"systemMessage": "consider a \"CHANGELOG\" entry"}))
+        );
+    }
+
+    #[test]
+    fn test_stop_output_block_with_warnings() {
+        let results = vec![
+            result("tests", Some("run cargo test"), Level::Block),
+            result("changelog", Some("add a changelog entry"), Level::Warn),
+        ];
+        assert_eq!(
+            stop_output(&results, ReasonFormat::Inline),
+            Some(serde_json::json!({
+                "decision": "block",
+                "reason": "run cargo test",
+                "rufio_reasons": [{"check": "tests", "message": "run cargo test"}],
+                "systemMessage": "add a changelog entry",
+            }))
+        );
+    }
+
+    #[test]
+    fn test_recheck_repeated_block() {
+        let block = serde_json::json!({"decision": "block", "reason": "run cargo test"});
+        let previous = LastBlock {
+            reason: "run cargo test".to_string(),
+            at_ms: 10_000,
+        };
+        let passes = || Ok(None);
+
+        // Same reason moments later: the re-evaluation wins
+        let output =
+            recheck_repeated_block(Some(block.clone()), Some(&previous), 12_000, passes).unwrap();
+        assert_eq!(output, None);
+
+        // ...but only if it now passes
+        let output = recheck_repeated_block(Some(block.clone()), Some(&previous), 12_000, || {
+            Ok(Some(block.clone()))
+        })
+        .unwrap();
+        assert_eq!(output, Some(block.clone()));
+
+        // Outside the grace period, or for a different reason, no re-evaluation
+        let unexpected = || -> Result<Option<serde_json::Value>> { panic!("re-evaluated") };
+        let output =
+            recheck_repeated_block(Some(block.clone()), Some(&previous), 20_000, unexpected)
+                .unwrap();
+        assert_eq!(output, Some(block.clone()));
+
+        let other = serde_json::json!({"decision": "block", "reason": "bump version.toml"});
+        let output =
+            recheck_repeated_block(Some(other.clone()), Some(&previous), 12_000, unexpected)
+                .unwrap();
+        assert_eq!(output, Some(other));
+
+        let output = recheck_repeated_block(Some(block.clone()), None, 12_000, unexpected).unwrap();
+        assert_eq!(output, Some(block));
+    }
+
+    #[test]
+    fn test_deadline() {
+        assert!(Deadline::none().check("running checks").is_ok());
+        assert!(Deadline::after(Duration::from_secs(60))
+            .check("running checks")
+            .is_ok());
+
+        // A spent budget stops at the next step boundary
+        let err = Deadline::after(Duration::ZERO)
+            .check("reading the transcript")
+            .unwrap_err();
+        let exceeded = err.downcast_ref::<DeadlineExceeded>().unwrap();
+        assert_eq!(exceeded.step, "reading the transcript");
+        assert_eq!(
+            err.to_string(),
+            "ran out of time (0ms) before reading the transcript"
+        );
+    }
+
+    #[test]
+    fn test_stop_timed_out_never_blocks() {
+        let input = read_input(
+            r#"{"hook_event_name":"Stop","cwd":"/nonexistent","session_id":"s","transcript_path":"/nonexistent"}"#
+                .as_bytes(),
+        )
+        .unwrap();
+        let err = Deadline::after(Duration::ZERO)
+            .check("running checks")
+            .unwrap_err();
+
+        // Built by hand so neither RUFIO_CONFIG nor the runtime dir is consulted
+        let session_dir = TempDir::new().unwrap();
+        let ctx = EventContext {
+            input: &input,
+            session_dir: session_dir.path(),
+            roots: GitRoots::default(),
+            config_override: None,
+            cwd_config: None,
+        };
+        let mut out = Vec::new();
+        assert_eq!(stop_timed_out(&ctx, &err, &mut out).unwrap(), None);
+        let output: serde_json::Value = serde_json::from_slice(&out).unwrap();
+        assert_eq!(
+            output,
+            serde_json::json!({
+                "systemMessage": "rufio skipped its checks: ran out of time (0ms) before running checks"
+            })
+        );
+    }
+
+    #[test]
+    fn test_sanitize_reason() {
+        assert_eq!(
+            sanitize_reason("\x1b[31mcargo test\x1b[0m failed\r"),
+            "cargo test failed"
+        );
+        assert_eq!(
+            sanitize_reason(
+                "\x1b[1;38;5;196mbold\x1b[m and \x1b]8;;https://x\x07link\x1b]8;;\x1b\\"
+            ),
+            "bold and link"
+        );
+        assert_eq!(
+            sanitize_reason("- one\n- two\twith tab\x07"),
+            "- one\n- two\twith tab"
+        );
+        assert_eq!(sanitize_reason("plain ünïcode"), "plain ünïcode");
+    }
+
+    #[test]
+    fn test_stop_output_strips_ansi() {
+        let results = vec![
+            result("tests", Some("run \x1b[31mcargo test\x1b[0m"), Level::Block),
+            result("lint", Some("\x1b[33mwarning\x1b[0m"), Level::Warn),
+        ];
+        let output = stop_output(&results, ReasonFormat::Inline).unwrap();
+        assert_eq!(output["reason"], "run cargo test");
+        assert_eq!(output["rufio_reasons"][0]["message"], "run cargo test");
+        assert_eq!(output["systemMessage"], "warning");
+    }
+
+    #[test]
+    fn test_stop_output_per_check_reasons() {
+        let results = vec![
+            result("tests", Some("run cargo test"), Level::Block),
+            result("lint", None, Level::Block),
+            result("version", Some("bump version.toml"), Level::Block),
+        ];
+        let output = stop_output(&results, ReasonFormat::Inline).unwrap();
+        assert_eq!(output["decision"], "block");
+        assert_eq!(output["reason"], "run cargo test | bump version.toml");
+
+        let reasons: Vec<CheckReason> =
+            serde_json::from_value(output["rufio_reasons"].clone()).unwrap();
+        assert_eq!(
+            reasons,
+            vec![
+                CheckReason {
+                    check: "tests".to_string(),
+                    message: "run cargo test".to_string(),
+                },
+                CheckReason {
+                    check: "version".to_string(),
+                    message: "bump version.toml".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_stop_output_list_format() {
+        let results = vec![
+            result("tests", Some("run \"cargo test\""), Level::Block),
+            result("lint", Some("run cargo clippy"), Level::Block),
+            result("version", Some("bump version.toml"), Level::Block),
+        ];
+        let output = stop_output(&results, ReasonFormat::List).unwrap();
+        assert_eq!(
+            output["reason"],
+            "- run \"cargo test\"\n- run cargo clippy\n- bump version.toml"
+        );
+
+        // Still a single valid JSON line on stdout
+        let line = output.to_string();
+        assert!(!line.contains('\n'));
+        let parsed: serde_json::Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(parsed, output);
+    }
+}
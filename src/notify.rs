@@ -0,0 +1,50 @@
+use std::process::Command;
+use tracing::debug;
+
+/// Build the notification text for a project and what happened in it
+pub fn format_message(project: &str, what: &str) -> String {
+    format!("{}: {}", project, what)
+}
+
+/// Send a desktop notification via notify-send (Linux) or osascript (macOS).
+/// Fails silently if the notifier binary is missing or errors.
+pub fn send(message: &str) {
+    let result = if cfg!(target_os = "macos") {
+        let script = format!(
+            r#"display notification "{}" with title "rufio""#,
+            escape_applescript(message)
+        );
+        Command::new("osascript").args(["-e", &script]).output()
+    } else {
+        Command::new("notify-send")
+            .args(["rufio", message])
+            .output()
+    };
+
+    if let Err(e) = result {
+        debug!(error = %e, "notification failed");
+    }
+}
+
+/// Escape a string for use inside an AppleScript string literal
+fn escape_applescript(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_message() {
+        assert_eq!(
+            format_message("rufio", "finished"),
+            "rufio: finished".to_string()
+        );
+    }
+
+    #[test]
+    fn test_escape_applescript() {
+        assert_eq!(escape_applescript(r#"say "hi" \o/"#), r#"say \"hi\" \\o/"#);
+    }
+}
@@ -0,0 +1,183 @@
+use crate::checks::CheckResult;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tracing::debug;
+
+/// Directory for per-session state: $XDG_RUNTIME_DIR, else the temp dir
+pub fn runtime_dir() -> PathBuf {
+    std::env::var("XDG_RUNTIME_DIR")
+        .ok()
+        .filter(|d| !d.is_empty())
+        .map(PathBuf::from)
+        .unwrap_or_else(std::env::temp_dir)
+}
+
+/// Path of a per-session state file, e.g. rufio-cache-{session_id}.json.
+/// The session id is sanitized so it can't escape the directory.
+pub fn session_file(dir: &Path, kind: &str, session_id: &str) -> PathBuf {
+    let safe_id: String = session_id
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    dir.join(format!("rufio-{}-{}.json", kind, safe_id))
+}
+
+/// Git lookups cached for the lifetime of a session
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct SessionCache {
+    /// cwd the cache was built for; a different cwd invalidates it
+    pub cwd: String,
+    /// Repository root of cwd, None outside any repository
+    pub git_root: Option<PathBuf>,
+    /// Working tree of the superproject when cwd is inside a submodule
+    #[serde(default)]
+    pub superproject_root: Option<PathBuf>,
+}
+
+impl SessionCache {
+    /// Load the cache for a session, ignoring it if missing, corrupt or for another cwd
+    pub fn load(dir: &Path, session_id: &str, cwd: &str) -> Option<SessionCache> {
+        let content = fs::read_to_string(session_file(dir, "cache", session_id)).ok()?;
+        let cache: SessionCache = serde_json::from_str(&content).ok()?;
+        (cache.cwd == cwd).then_some(cache)
+    }
+
+    /// Best-effort write of the cache for a session
+    pub fn store(&self, dir: &Path, session_id: &str) {
+        let path = session_file(dir, "cache", session_id);
+        let result = serde_json::to_string(self)
+            .map_err(std::io::Error::from)
+            .and_then(|json| fs::write(&path, json));
+        if let Err(e) = result {
+            debug!(path = %path.display(), error = %e, "failed to write session cache");
+        }
+    }
+}
+
+/// The most recent Stop block in a session, to spot an identical re-block
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LastBlock {
+    /// The block reason as sent to Claude
+    pub reason: String,
+    /// When it was sent, in milliseconds since the Unix epoch
+    pub at_ms: u64,
+}
+
+impl LastBlock {
+    pub fn load(dir: &Path, session_id: &str) -> Option<LastBlock> {
+        let content = fs::read_to_string(session_file(dir, "block", session_id)).ok()?;
+        serde_json::from_str(&content).ok()
+    }
+
+    /// Best-effort write of the last block for a session
+    pub fn store(&self, dir: &Path, session_id: &str) {
+        let path = session_file(dir, "block", session_id);
+        let result = serde_json::to_string(self)
+            .map_err(std::io::Error::from)
+            .and_then(|json| fs::write(&path, json));
+        if let Err(e) = result {
+            debug!(path = %path.display(), error = %e, "failed to write last block");
+        }
+    }
+
+    /// Forget the last block once a Stop goes through
+    pub fn clear(dir: &Path, session_id: &str) {
+        let _ = fs::remove_file(session_file(dir, "block", session_id));
+    }
+}
+
+/// The outcome of a session's latest Stop, written to rufio-result-{session_id}.json
+/// for tools such as status bars
+#[derive(Debug, Serialize)]
+pub struct StopSummary<'a> {
+    /// When the Stop was evaluated, in milliseconds since the Unix epoch
+    pub at_ms: u64,
+    /// Whether the Stop was blocked
+    pub blocked: bool,
+    pub results: &'a [CheckResult],
+}
+
+impl StopSummary<'_> {
+    /// Best-effort write of the summary for a session
+    pub fn store(&self, dir: &Path, session_id: &str) {
+        let path = session_file(dir, "result", session_id);
+        let result = serde_json::to_string(self)
+            .map_err(std::io::Error::from)
+            .and_then(|json| fs::write(&path, json));
+        if let Err(e) = result {
+            debug!(path = %path.display(), error = %e, "failed to write stop summary");
+        }
+    }
+}
+
+/// Milliseconds since the Unix epoch
+pub fn now_ms() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_session_cache_round_trip() {
+        let temp = TempDir::new().unwrap();
+        let cache = SessionCache {
+            cwd: "/repo/projects/foo".to_string(),
+            git_root: Some(PathBuf::from("/repo")),
+            superproject_root: None,
+        };
+        cache.store(temp.path(), "abc-123");
+
+        let loaded = SessionCache::load(temp.path(), "abc-123", "/repo/projects/foo");
+        assert_eq!(loaded, Some(cache));
+    }
+
+    #[test]
+    fn test_session_cache_invalidated_by_cwd() {
+        let temp = TempDir::new().unwrap();
+        let cache = SessionCache {
+            cwd: "/repo".to_string(),
+            git_root: Some(PathBuf::from("/repo")),
+            superproject_root: None,
+        };
+        cache.store(temp.path(), "abc-123");
+
+        assert_eq!(SessionCache::load(temp.path(), "abc-123", "/other"), None);
+        assert_eq!(SessionCache::load(temp.path(), "missing", "/repo"), None);
+    }
+
+    #[test]
+    fn test_last_block_round_trip() {
+        let temp = TempDir::new().unwrap();
+        let block = LastBlock {
+            reason: "run cargo test".to_string(),
+            at_ms: 1_700_000_000_000,
+        };
+        block.store(temp.path(), "abc-123");
+        assert_eq!(LastBlock::load(temp.path(), "abc-123"), Some(block));
+
+        LastBlock::clear(temp.path(), "abc-123");
+        assert_eq!(LastBlock::load(temp.path(), "abc-123"), None);
+    }
+
+    #[test]
+    fn test_session_file_sanitizes_id() {
+        let path = session_file(Path::new("/run"), "cache", "../../etc/passwd");
+        assert_eq!(
+            path,
+            PathBuf::from("/run/rufio-cache-______etc_passwd.json")
+        );
+    }
+}
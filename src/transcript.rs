@@ -1,9 +1,12 @@
+use crate::config::ToolMapping;
 use anyhow::Result;
-use serde::Deserialize;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Deserializer};
 use serde_json::Value;
+use std::collections::HashMap;
 use std::fs::File;
 use std::io::{BufRead, BufReader};
-use std::path::Path;
+use std::path::{Component, Path, PathBuf};
 
 /// Represents a tool use content item
 #[derive(Debug, Deserialize)]
@@ -14,18 +17,68 @@ struct ToolUse {
     input: Option<Value>,
 }
 
+/// A message's content: plain text, or a list of content blocks
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum Content {
+    Text(String),
+    /// Blocks stay raw so one odd block doesn't hide the others
+    Blocks(Vec<Value>),
+}
+
 /// Represents the message content
 #[derive(Debug, Deserialize)]
 struct MessageContent {
-    content: Option<Vec<ToolUse>>,
+    #[serde(default, deserialize_with = "lenient")]
+    content: Option<Content>,
+}
+
+/// `message` is normally one object, but some lines carry a list of them
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum Messages {
+    One(MessageContent),
+    Many(Vec<MessageContent>),
+}
+
+impl Messages {
+    fn contents(&self) -> impl Iterator<Item = &Content> {
+        let messages = match self {
+            Messages::One(message) => std::slice::from_ref(message),
+            Messages::Many(messages) => messages.as_slice(),
+        };
+        messages.iter().filter_map(|m| m.content.as_ref())
+    }
 }
 
 /// Represents a transcript line with message
 #[derive(Debug, Deserialize)]
 struct TranscriptLine {
-    message: Option<MessageContent>,
+    #[serde(rename = "type", default, deserialize_with = "lenient")]
+    type_: Option<String>,
+    #[serde(default, deserialize_with = "lenient")]
+    message: Option<Messages>,
+    /// Set on lines Claude Code adds for its own bookkeeping, e.g. caveats
+    #[serde(rename = "isMeta", default, deserialize_with = "lenient")]
+    is_meta: Option<bool>,
+    /// Kept as a raw value so an unexpected type doesn't drop the line
+    timestamp: Option<Value>,
 }
 
+/// Deserialize a field as None when it has an unexpected shape, instead of
+/// failing the whole line
+fn lenient<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    let value = Value::deserialize(deserializer)?;
+    Ok(T::deserialize(value).ok())
+}
+
+/// Tools whose `file_path` input is a file they wrote
+pub const EDIT_TOOLS: [&str; 3] = ["Edit", "Write", "MultiEdit"];
+
 /// A tool use event extracted from the transcript
 #[derive(Debug, Clone)]
 pub struct ToolUseEvent {
@@ -33,78 +86,446 @@ pub struct ToolUseEvent {
     pub command: Option<String>,
     pub file_path: Option<String>,
     pub index: usize,
+    /// Number of user prompts seen before this event (0 before the first)
+    pub turn: usize,
+    /// When the event was logged, if the line carried a valid timestamp
+    pub timestamp: Option<DateTime<Utc>>,
+}
+
+impl ToolUseEvent {
+    /// Whether this event wrote a file (Edit, Write or MultiEdit)
+    pub fn is_edit(&self) -> bool {
+        EDIT_TOOLS.contains(&self.tool_name.as_str())
+    }
+}
+
+/// Tool use events extracted from a transcript, with turn boundaries
+#[derive(Debug, Default)]
+pub struct Transcript {
+    pub events: Vec<ToolUseEvent>,
+    /// Turn number of the most recent user prompt
+    pub current_turn: usize,
 }
 
-/// Extract all tool use events from a transcript file, in order
-pub fn extract_tool_events(transcript_path: &str) -> Result<Vec<ToolUseEvent>> {
+/// Starts of user messages that Claude Code writes itself: interrupt
+/// markers and the output of local slash commands
+const SYNTHETIC_USER_TEXT: [&str; 4] = [
+    "[Request interrupted by user",
+    "<local-command-stdout>",
+    "<local-command-stderr>",
+    "<local-command-caveat>",
+];
+
+fn is_synthetic(text: &str) -> bool {
+    let text = text.trim_start();
+    SYNTHETIC_USER_TEXT
+        .iter()
+        .any(|prefix| text.starts_with(prefix))
+}
+
+/// A user prompt starts a new turn. Tool results are also recorded as user
+/// messages, but always as tool_result blocks; meta lines and synthetic
+/// messages such as interrupt markers don't count either.
+fn is_user_prompt(entry: &TranscriptLine) -> bool {
+    if entry.type_.as_deref() != Some("user") || entry.is_meta == Some(true) {
+        return false;
+    }
+
+    let Some(messages) = &entry.message else {
+        return false;
+    };
+    messages.contents().any(|content| match content {
+        Content::Text(text) => !is_synthetic(text),
+        Content::Blocks(blocks) => {
+            !blocks
+                .iter()
+                .any(|b| b.get("type").and_then(Value::as_str) == Some("tool_result"))
+                && !blocks.iter().all(|b| {
+                    b.get("text")
+                        .and_then(Value::as_str)
+                        .is_some_and(is_synthetic)
+                })
+        }
+    })
+}
+
+/// Parse an RFC 3339 timestamp, ignoring anything malformed
+fn parse_timestamp(value: Option<&Value>) -> Option<DateTime<Utc>> {
+    let raw = value?.as_str()?;
+    DateTime::parse_from_rfc3339(raw)
+        .ok()
+        .map(|t| t.with_timezone(&Utc))
+}
+
+/// Extract all tool use events from a transcript file, in order.
+/// Tools named in `tool_mappings` are recorded as the built-in tool they map to.
+pub fn extract_tool_events(
+    transcript_path: &str,
+    tool_mappings: &HashMap<String, ToolMapping>,
+) -> Result<Transcript> {
     let path = Path::new(transcript_path);
     if !path.exists() {
-        return Ok(Vec::new());
+        return Ok(Transcript::default());
     }
 
     let file = File::open(path)?;
     let reader = BufReader::new(file);
     let mut events = Vec::new();
     let mut index = 0;
+    let mut turn = 0;
 
-    for line in reader.lines() {
+    // Split on raw bytes: tool output can contain invalid UTF-8, which
+    // should cost at most that line rather than the whole transcript
+    for line in reader.split(b'\n') {
         let line = line?;
-        if line.is_empty() {
+        let line = String::from_utf8_lossy(&line);
+        if line.trim().is_empty() {
             continue;
         }
 
         if let Ok(entry) = serde_json::from_str::<TranscriptLine>(&line) {
-            if let Some(message) = entry.message {
-                if let Some(content) = message.content {
-                    for item in content {
-                        if item.type_.as_deref() == Some("tool_use") {
-                            if let Some(name) = &item.name {
-                                let mut event = ToolUseEvent {
-                                    tool_name: name.clone(),
-                                    command: None,
-                                    file_path: None,
-                                    index,
-                                };
-
-                                // Extract relevant fields from input based on tool type
-                                if let Some(input) = &item.input {
-                                    match name.as_str() {
-                                        "Bash" => {
-                                            event.command = input
-                                                .get("command")
-                                                .and_then(|v| v.as_str())
-                                                .map(String::from);
-                                        }
-                                        "Edit" | "Write" => {
-                                            event.file_path = input
-                                                .get("file_path")
-                                                .and_then(|v| v.as_str())
-                                                .map(String::from);
-                                        }
-                                        _ => {}
-                                    }
-                                }
+            if is_user_prompt(&entry) {
+                turn += 1;
+                continue;
+            }
 
-                                events.push(event);
-                                index += 1;
+            let timestamp = parse_timestamp(entry.timestamp.as_ref());
+            let Some(messages) = &entry.message else {
+                continue;
+            };
+            let blocks = messages.contents().flat_map(|content| match content {
+                Content::Text(_) => [].iter(),
+                Content::Blocks(blocks) => blocks.iter(),
+            });
+
+            for block in blocks {
+                let Ok(item) = ToolUse::deserialize(block) else {
+                    continue;
+                };
+                if item.type_.as_deref() == Some("tool_use") {
+                    if let Some(name) = &item.name {
+                        let (name, key) = match tool_mappings.get(name) {
+                            Some(mapping) => (mapping.tool.name(), Some(mapping.key())),
+                            None => (name.as_str(), None),
+                        };
+                        let mut event = ToolUseEvent {
+                            tool_name: name.to_string(),
+                            command: None,
+                            file_path: None,
+                            index,
+                            turn,
+                            timestamp,
+                        };
+
+                        // Extract relevant fields from input based on tool type
+                        if let Some(input) = &item.input {
+                            let field = |default: &str| {
+                                input
+                                    .get(key.unwrap_or(default))
+                                    .and_then(|v| v.as_str())
+                                    .map(String::from)
+                            };
+                            match name {
+                                "Bash" => event.command = field("command"),
+                                name if EDIT_TOOLS.contains(&name) => {
+                                    event.file_path = field("file_path")
+                                }
+                                _ => {}
                             }
                         }
+
+                        events.push(event);
+                        index += 1;
                     }
                 }
             }
         }
     }
 
-    Ok(events)
+    Ok(Transcript {
+        events,
+        current_turn: turn,
+    })
+}
+
+/// Rewrite event file paths under `root` to be relative to it, so they
+/// share the form of the changed files reported by git. Relative paths are
+/// taken as already relative to `root`; paths outside it stay absolute.
+pub fn relativize_paths(events: &mut [ToolUseEvent], root: &Path) {
+    for event in events {
+        let Some(path) = &event.file_path else {
+            continue;
+        };
+        let normalized = normalize_lexically(&root.join(path));
+        if let Ok(relative) = normalized.strip_prefix(root) {
+            event.file_path = Some(relative.to_string_lossy().to_string());
+        }
+    }
+}
+
+/// Resolve `.` and `..` components without touching the filesystem
+fn normalize_lexically(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            other => normalized.push(other),
+        }
+    }
+    normalized
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::fs;
+    use tempfile::TempDir;
 
     #[test]
     fn test_nonexistent_transcript() {
-        let events = extract_tool_events("/nonexistent/path.jsonl").unwrap();
-        assert!(events.is_empty());
+        let transcript = extract_tool_events("/nonexistent/path.jsonl", &HashMap::new()).unwrap();
+        assert!(transcript.events.is_empty());
+        assert_eq!(transcript.current_turn, 0);
+    }
+
+    #[test]
+    fn test_turn_boundaries() {
+        let temp = TempDir::new().unwrap();
+        let path = temp.path().join("transcript.jsonl");
+        let lines = [
+            r#"{"type":"user","message":{"role":"user","content":"add a feature"}}"#,
+            r#"{"type":"assistant","message":{"content":[{"type":"tool_use","name":"Edit","input":{"file_path":"/repo/src/main.rs"}}]}}"#,
+            r#"{"type":"user","message":{"role":"user","content":[{"type":"tool_result","content":"ok"}]}}"#,
+            r#"{"type":"assistant","message":{"content":[{"type":"tool_use","name":"Bash","input":{"command":"cargo test"}}]}}"#,
+            r#"{"type":"user","message":{"role":"user","content":[{"type":"text","text":"now fix the docs"}]}}"#,
+            r#"{"type":"assistant","message":{"content":[{"type":"tool_use","name":"Write","input":{"file_path":"/repo/README.md"}}]}}"#,
+        ];
+        fs::write(&path, lines.join("\n")).unwrap();
+
+        let transcript = extract_tool_events(path.to_str().unwrap(), &HashMap::new()).unwrap();
+        let turns: Vec<(&str, usize)> = transcript
+            .events
+            .iter()
+            .map(|e| (e.tool_name.as_str(), e.turn))
+            .collect();
+        assert_eq!(turns, vec![("Edit", 1), ("Bash", 1), ("Write", 2)]);
+        assert_eq!(transcript.current_turn, 2);
+    }
+
+    #[test]
+    fn test_synthetic_user_messages_keep_the_turn() {
+        let temp = TempDir::new().unwrap();
+        let path = temp.path().join("transcript.jsonl");
+        let lines = [
+            r#"{"type":"user","message":{"role":"user","content":"add a feature"}}"#,
+            r#"{"type":"assistant","message":{"content":[{"type":"tool_use","name":"Edit","input":{"file_path":"/repo/src/main.rs"}}]}}"#,
+            r#"{"type":"user","message":{"role":"user","content":[{"type":"text","text":"[Request interrupted by user for tool use]"}]}}"#,
+            r#"{"type":"user","message":{"role":"user","content":"[Request interrupted by user]"}}"#,
+            r#"{"type":"user","isMeta":true,"message":{"role":"user","content":"Caveat: The messages below were generated by the user while running local commands."}}"#,
+            r#"{"type":"user","message":{"role":"user","content":"<local-command-stdout>Set model to opus</local-command-stdout>"}}"#,
+            r#"{"type":"assistant","message":{"content":[{"type":"tool_use","name":"Bash","input":{"command":"cargo test"}}]}}"#,
+            r#"{"type":"user","message":{"role":"user","content":[{"type":"text","text":"now fix the docs"}]}}"#,
+            r#"{"type":"assistant","message":{"content":[{"type":"tool_use","name":"Write","input":{"file_path":"/repo/README.md"}}]}}"#,
+        ];
+        fs::write(&path, lines.join("\n")).unwrap();
+
+        let transcript = extract_tool_events(path.to_str().unwrap(), &HashMap::new()).unwrap();
+        let turns: Vec<(&str, usize)> = transcript
+            .events
+            .iter()
+            .map(|e| (e.tool_name.as_str(), e.turn))
+            .collect();
+        assert_eq!(turns, vec![("Edit", 1), ("Bash", 1), ("Write", 2)]);
+        assert_eq!(transcript.current_turn, 2);
+    }
+
+    #[test]
+    fn test_multi_edit_event() {
+        let temp = TempDir::new().unwrap();
+        let path = temp.path().join("transcript.jsonl");
+        let line = r#"{"message":{"content":[{"type":"tool_use","name":"MultiEdit","input":{"file_path":"/repo/src/lib.rs","edits":[{"old_string":"a","new_string":"b"}]}}]}}"#;
+        fs::write(&path, line).unwrap();
+
+        let events = extract_tool_events(path.to_str().unwrap(), &HashMap::new())
+            .unwrap()
+            .events;
+        assert_eq!(events[0].tool_name, "MultiEdit");
+        assert_eq!(events[0].file_path.as_deref(), Some("/repo/src/lib.rs"));
+        assert!(events[0].is_edit());
+    }
+
+    #[test]
+    fn test_custom_tool_mappings() {
+        let temp = TempDir::new().unwrap();
+        let path = temp.path().join("transcript.jsonl");
+        let lines = [
+            r#"{"message":{"content":[{"type":"tool_use","name":"mcp__fs__write_file","input":{"path":"/repo/src/main.rs","content":"fn main() {}"}}]}}"#,
+            r#"{"message":{"content":[{"type":"tool_use","name":"mcp__shell__exec","input":{"command":"cargo test"}}]}}"#,
+            r#"{"message":{"content":[{"type":"tool_use","name":"Write","input":{"file_path":"/repo/README.md"}}]}}"#,
+        ];
+        fs::write(&path, lines.join("\n")).unwrap();
+        let path = path.to_str().unwrap();
+
+        // Without mappings the custom tools carry no path or command
+        let events = extract_tool_events(path, &HashMap::new()).unwrap().events;
+        assert_eq!(events[0].tool_name, "mcp__fs__write_file");
+        assert_eq!(events[0].file_path, None);
+
+        let mappings: HashMap<String, ToolMapping> = serde_yaml::from_str(
+            r#"
+mcp__fs__write_file: {as: Write, key: path}
+mcp__shell__exec: {as: Bash}
+"#,
+        )
+        .unwrap();
+        let events = extract_tool_events(path, &mappings).unwrap().events;
+        let extracted: Vec<(&str, Option<&str>, Option<&str>)> = events
+            .iter()
+            .map(|e| {
+                (
+                    e.tool_name.as_str(),
+                    e.file_path.as_deref(),
+                    e.command.as_deref(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            extracted,
+            vec![
+                ("Write", Some("/repo/src/main.rs"), None),
+                ("Bash", None, Some("cargo test")),
+                ("Write", Some("/repo/README.md"), None),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_event_timestamps() {
+        let temp = TempDir::new().unwrap();
+        let path = temp.path().join("transcript.jsonl");
+        let lines = [
+            r#"{"timestamp":"2025-06-01T12:34:56.789Z","message":{"content":[{"type":"tool_use","name":"Bash","input":{"command":"cargo test"}}]}}"#,
+            r#"{"timestamp":"yesterday","message":{"content":[{"type":"tool_use","name":"Bash","input":{"command":"cargo build"}}]}}"#,
+            r#"{"timestamp":42,"message":{"content":[{"type":"tool_use","name":"Bash","input":{"command":"cargo fmt"}}]}}"#,
+            r#"{"message":{"content":[{"type":"tool_use","name":"Bash","input":{"command":"cargo clippy"}}]}}"#,
+        ];
+        fs::write(&path, lines.join("\n")).unwrap();
+
+        let events = extract_tool_events(path.to_str().unwrap(), &HashMap::new())
+            .unwrap()
+            .events;
+        assert_eq!(events.len(), 4);
+        let expected = DateTime::parse_from_rfc3339("2025-06-01T12:34:56.789+00:00").unwrap();
+        assert_eq!(events[0].timestamp, Some(expected.with_timezone(&Utc)));
+        assert!(events[1..].iter().all(|e| e.timestamp.is_none()));
+    }
+
+    #[test]
+    fn test_relativize_paths() {
+        let event = |path: &str| ToolUseEvent {
+            tool_name: "Edit".to_string(),
+            command: None,
+            file_path: Some(path.to_string()),
+            index: 0,
+            turn: 0,
+            timestamp: None,
+        };
+        let mut events = vec![
+            event("/repo/src/main.rs"),
+            event("src/lib.rs"),
+            event("/repo/docs/../README.md"),
+            event("/elsewhere/notes.md"),
+        ];
+
+        relativize_paths(&mut events, Path::new("/repo"));
+        let paths: Vec<&str> = events
+            .iter()
+            .filter_map(|e| e.file_path.as_deref())
+            .collect();
+        assert_eq!(
+            paths,
+            vec![
+                "src/main.rs",
+                "src/lib.rs",
+                "README.md",
+                "/elsewhere/notes.md"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_mixed_content_shapes() {
+        let temp = TempDir::new().unwrap();
+        let path = temp.path().join("transcript.jsonl");
+        let lines = [
+            r#"{"type":"assistant","message":{"content":"Let me look at that."}}"#,
+            r#"{"type":"assistant","message":{"content":[{"type":"text","text":"Editing"},{"type":"tool_use","name":"Edit","input":{"file_path":"/repo/a.rs"}}]}}"#,
+            r#"{"type":"assistant","message":[{"content":"thinking"},{"content":[{"type":"tool_use","name":"Bash","input":{"command":"cargo build"}}]}]}"#,
+            r#"{"type":"assistant","message":{"content":42}}"#,
+            r#"{"type":"assistant","message":"not an object"}"#,
+            r#"{"type":["odd"],"message":{"content":[{"type":"tool_use","name":"Write","input":{"file_path":"/repo/b.rs"}}]}}"#,
+            r#"{"type":"assistant","message":{"content":[{"type":"tool_use","name":"Bash","input":{"command":"cargo test"}}]}}"#,
+        ];
+        fs::write(&path, lines.join("\n")).unwrap();
+
+        let events = extract_tool_events(path.to_str().unwrap(), &HashMap::new())
+            .unwrap()
+            .events;
+        let summary: Vec<(&str, Option<&str>)> = events
+            .iter()
+            .map(|e| {
+                (
+                    e.tool_name.as_str(),
+                    e.command.as_deref().or(e.file_path.as_deref()),
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("Edit", Some("/repo/a.rs")),
+                ("Bash", Some("cargo build")),
+                ("Write", Some("/repo/b.rs")),
+                ("Bash", Some("cargo test")),
+            ]
+        );
+        let indexes: Vec<usize> = events.iter().map(|e| e.index).collect();
+        assert_eq!(indexes, vec![0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn test_invalid_utf8_line() {
+        let temp = TempDir::new().unwrap();
+        let path = temp.path().join("transcript.jsonl");
+        let mut content = Vec::new();
+        content.extend_from_slice(
+            br#"{"message":{"content":[{"type":"tool_use","name":"Edit","input":{"file_path":"/repo/a.rs"}}]}}"#,
+        );
+        content.push(b'\n');
+        content.extend_from_slice(
+            br#"{"type":"user","message":{"content":[{"type":"tool_result","content":"bad "#,
+        );
+        content.extend_from_slice(&[0xff, 0xfe]);
+        content.extend_from_slice(br#" bytes"}]}}"#);
+        content.push(b'\n');
+        content.extend_from_slice(
+            br#"{"message":{"content":[{"type":"tool_use","name":"Bash","input":{"command":"cargo test"}}]}}"#,
+        );
+        content.push(b'\n');
+        content.extend_from_slice(&[0xc3, 0x28, b'\n']);
+        content.extend_from_slice(
+            br#"{"message":{"content":[{"type":"tool_use","name":"Bash","input":{"command":"cargo build"}}]}}"#,
+        );
+        fs::write(&path, content).unwrap();
+
+        let events = extract_tool_events(path.to_str().unwrap(), &HashMap::new())
+            .unwrap()
+            .events;
+        let names: Vec<&str> = events.iter().map(|e| e.tool_name.as_str()).collect();
+        assert_eq!(names, vec!["Edit", "Bash", "Bash"]);
+        assert_eq!(events[2].command.as_deref(), Some("cargo build"));
     }
 }
@@ -1,11 +1,26 @@
 #![allow(clippy::expect_used, clippy::unwrap_used)]
 
+use std::fs;
 use std::io::Write;
+use std::path::Path;
 use std::process::{Command, Stdio};
+use tempfile::TempDir;
 
 fn run_rufio(json: &str) -> (String, String, i32) {
-    let mut child = Command::new("cargo")
-        .args(["run", "--quiet"])
+    run_rufio_with_args(&[], json)
+}
+
+fn run_rufio_with_args(args: &[&str], json: &str) -> (String, String, i32) {
+    run_rufio_with_env(args, &[], json)
+}
+
+fn run_rufio_with_env(args: &[&str], env: &[(&str, &str)], json: &str) -> (String, String, i32) {
+    // Keep session state out of the real runtime dir unless the test picks one
+    let runtime_dir = TempDir::new().expect("failed to create temp dir");
+    let mut child = isolated(Command::new("cargo").args(["run", "--quiet", "--"]))
+        .args(args)
+        .env("XDG_RUNTIME_DIR", runtime_dir.path())
+        .envs(env.iter().copied())
         .stdin(Stdio::piped())
         .stdout(Stdio::piped())
         .stderr(Stdio::piped())
@@ -25,11 +40,606 @@ fn run_rufio(json: &str) -> (String, String, i32) {
     )
 }
 
+/// Keep a developer's global config, presets and env overrides out of a
+/// rufio run, so only what a test sets explicitly applies.
+fn isolated(command: &mut Command) -> &mut Command {
+    command.env("XDG_CONFIG_HOME", "/nonexistent");
+    for var in [
+        "RUFIO_CONFIG",
+        "RUFIO_DIFF_MODE",
+        "RUFIO_DEADLINE_MS",
+        "RUFIO_REPO_ROOT",
+    ] {
+        command.env_remove(var);
+    }
+    command
+}
+
+/// Initialise a git repo with the given files (all left uncommitted).
+fn init_repo(files: &[(&str, &str)]) -> TempDir {
+    let temp = TempDir::new().expect("failed to create temp dir");
+    let status = Command::new("git")
+        .args(["init", "--quiet"])
+        .current_dir(temp.path())
+        .status()
+        .expect("failed to run git init");
+    assert!(status.success());
+
+    for (path, content) in files {
+        let full = temp.path().join(path);
+        if let Some(parent) = full.parent() {
+            fs::create_dir_all(parent).expect("failed to create dir");
+        }
+        fs::write(full, content).expect("failed to write file");
+    }
+    temp
+}
+
+/// Write a transcript where each entry is (tool, input JSON) and return its path.
+fn write_transcript(dir: &Path, tool_uses: &[(&str, serde_json::Value)]) -> String {
+    let lines: Vec<String> = tool_uses
+        .iter()
+        .map(|(name, input)| {
+            serde_json::json!({
+                "message": {"content": [{"type": "tool_use", "name": name, "input": input}]}
+            })
+            .to_string()
+        })
+        .collect();
+    let path = dir.join("transcript.jsonl");
+    fs::write(&path, lines.join("\n")).expect("failed to write transcript");
+    path.to_string_lossy().to_string()
+}
+
+/// A Stop hook input. Each test uses its own session id so no state is
+/// shared between tests.
+fn stop_input(session_id: &str, cwd: &Path, transcript_path: &str) -> String {
+    serde_json::json!({
+        "hook_event_name": "Stop",
+        "cwd": cwd,
+        "session_id": session_id,
+        "transcript_path": transcript_path,
+    })
+    .to_string()
+}
+
+const MIXED_CONFIG: &str = r#"
+checks:
+  - name: tests
+    when:
+      paths_changed: "**/*.rs"
+    then:
+      ensure_commands:
+        - cargo test
+  - name: version
+    when:
+      paths_changed: "**/*.rs"
+    then:
+      ensure_changed:
+        - version.toml
+"#;
+
+#[test]
+fn test_check_subcommand_json() {
+    let repo = init_repo(&[
+        ("rufio-hooks.yaml", MIXED_CONFIG),
+        ("src/main.rs", "fn main() {}"),
+    ]);
+    let transcript_dir = TempDir::new().expect("failed to create temp dir");
+    let main_rs = repo.path().join("src/main.rs");
+    let transcript = write_transcript(
+        transcript_dir.path(),
+        &[
+            ("Write", serde_json::json!({"file_path": main_rs})),
+            ("Bash", serde_json::json!({"command": "cargo test"})),
+        ],
+    );
+
+    let (stdout, stderr, code) = run_rufio_with_args(
+        &["check", "--format", "json"],
+        &stop_input("check-subcommand-json", repo.path(), &transcript),
+    );
+
+    assert_eq!(code, 0, "stderr: {stderr}");
+    let results: serde_json::Value = serde_json::from_str(&stdout).expect("invalid JSON");
+    let results = results.as_array().expect("expected an array");
+    assert_eq!(results.len(), 2);
+    assert_eq!(results[0]["check"], "tests");
+    assert_eq!(results[0]["passed"], true);
+    assert!(results[0]["reason"].is_null());
+    assert_eq!(results[1]["check"], "version");
+    assert_eq!(results[1]["passed"], false);
+    assert!(results[1]["reason"]
+        .as_str()
+        .expect("reason should be a string")
+        .contains("version.toml"));
+}
+
+#[test]
+fn test_stop_blocks() {
+    let repo = init_repo(&[
+        ("rufio-hooks.yaml", MIXED_CONFIG),
+        ("src/main.rs", "fn main() {}"),
+    ]);
+    let transcript_dir = TempDir::new().expect("failed to create temp dir");
+    let transcript = write_transcript(
+        transcript_dir.path(),
+        &[(
+            "Edit",
+            serde_json::json!({"file_path": repo.path().join("src/main.rs")}),
+        )],
+    );
+
+    let (stdout, stderr, code) = run_rufio(&stop_input("stop-blocks", repo.path(), &transcript));
+    assert_eq!(code, 0, "stderr: {stderr}");
+    let output: serde_json::Value = serde_json::from_str(&stdout).expect("invalid JSON");
+    assert_eq!(output["decision"], "block");
+    let reason = output["reason"]
+        .as_str()
+        .expect("reason should be a string");
+    assert!(reason.contains("cargo test"));
+    assert!(reason.contains("version.toml"));
+}
+
+#[test]
+fn test_rufioignore_disables_checks() {
+    let repo = init_repo(&[
+        ("rufio-hooks.yaml", MIXED_CONFIG),
+        (".rufioignore", ""),
+        ("src/main.rs", "fn main() {}"),
+    ]);
+    let transcript_dir = TempDir::new().expect("failed to create temp dir");
+    let transcript = write_transcript(
+        transcript_dir.path(),
+        &[(
+            "Edit",
+            serde_json::json!({"file_path": repo.path().join("src/main.rs")}),
+        )],
+    );
+    let stop = |cwd: &Path| {
+        let (stdout, stderr, code) =
+            run_rufio(&stop_input("rufioignore-disables-checks", cwd, &transcript));
+        assert_eq!(code, 0, "stderr: {stderr}");
+        stdout
+    };
+
+    // Applies from a subdirectory too
+    fs::create_dir_all(repo.path().join("src/nested")).expect("failed to create dir");
+    for cwd in [repo.path().to_path_buf(), repo.path().join("src/nested")] {
+        let stdout = stop(&cwd);
+        assert!(stdout.is_empty(), "stdout: {stdout}");
+    }
+
+    // Without the marker the same change blocks
+    fs::remove_file(repo.path().join(".rufioignore")).expect("failed to remove marker");
+    let stdout = stop(repo.path());
+    assert!(
+        stdout.contains("\"decision\":\"block\""),
+        "stdout: {stdout}"
+    );
+
+    // `disabled: true` in the config does the same
+    let config = format!("disabled: true\n{}", MIXED_CONFIG);
+    fs::write(repo.path().join("rufio-hooks.yaml"), config).expect("failed to write config");
+    let stdout = stop(repo.path());
+    assert!(stdout.is_empty(), "stdout: {stdout}");
+}
+
+#[test]
+fn test_write_results_summary() {
+    let config = format!("write_results: true\n{}", MIXED_CONFIG);
+    let repo = init_repo(&[
+        ("rufio-hooks.yaml", config.as_str()),
+        ("src/main.rs", "fn main() {}"),
+    ]);
+    let runtime_dir = TempDir::new().expect("failed to create temp dir");
+    let transcript = write_transcript(
+        runtime_dir.path(),
+        &[
+            (
+                "Edit",
+                serde_json::json!({"file_path": repo.path().join("src/main.rs")}),
+            ),
+            ("Bash", serde_json::json!({"command": "cargo test"})),
+        ],
+    );
+    let env = [(
+        "XDG_RUNTIME_DIR",
+        runtime_dir.path().to_str().expect("non-UTF-8 temp dir"),
+    )];
+
+    let (stdout, stderr, code) = run_rufio_with_env(
+        &[],
+        &env,
+        &stop_input("write-results-summary", repo.path(), &transcript),
+    );
+    assert_eq!(code, 0, "stderr: {stderr}");
+    assert!(
+        stdout.contains("\"decision\":\"block\""),
+        "stdout: {stdout}"
+    );
+
+    let summary = fs::read_to_string(
+        runtime_dir
+            .path()
+            .join("rufio-result-write-results-summary.json"),
+    )
+    .expect("results file should be written");
+    let summary: serde_json::Value = serde_json::from_str(&summary).expect("invalid JSON");
+    assert!(summary["at_ms"].as_u64().is_some_and(|at| at > 0));
+    assert_eq!(summary["blocked"], true);
+    assert_eq!(
+        summary["results"],
+        serde_json::json!([
+            {"check": "tests", "passed": true, "reason": null, "level": "block"},
+            {
+                "check": "version",
+                "passed": false,
+                "reason": "Check 'version' failed: one of these files must be changed when editing **/*.rs: version.toml",
+                "level": "block",
+            },
+        ])
+    );
+}
+
+#[test]
+fn test_rufio_config_env_override() {
+    // No rufio-hooks.yaml in the repo: only the RUFIO_CONFIG file applies
+    let repo = init_repo(&[("src/main.rs", "fn main() {}")]);
+    let config_dir = TempDir::new().expect("failed to create temp dir");
+    let config_path = config_dir.path().join("custom.yaml");
+    fs::write(&config_path, MIXED_CONFIG).expect("failed to write config");
+    let transcript = write_transcript(
+        config_dir.path(),
+        &[(
+            "Write",
+            serde_json::json!({"file_path": repo.path().join("src/main.rs")}),
+        )],
+    );
+    let input = stop_input("rufio-config-env-override", repo.path(), &transcript);
+
+    let (stdout, _stderr, code) = run_rufio(&input);
+    assert_eq!(code, 0);
+    assert!(
+        stdout.is_empty(),
+        "no config should mean no block: {stdout}"
+    );
+
+    let config_env = config_path.to_string_lossy().to_string();
+    let (stdout, stderr, code) =
+        run_rufio_with_env(&[], &[("RUFIO_CONFIG", config_env.as_str())], &input);
+    assert_eq!(code, 0, "stderr: {stderr}");
+    let decision: serde_json::Value = serde_json::from_str(&stdout).expect("invalid JSON");
+    assert_eq!(decision["decision"], "block");
+    let reason = decision["reason"]
+        .as_str()
+        .expect("reason should be a string");
+    assert!(reason.contains("cargo test"));
+    assert!(reason.contains("version.toml"));
+}
+
+#[test]
+fn test_rufio_config_env_override_from_subdirectory() {
+    // The override's paths are relative to the repo root, not the session cwd
+    let repo = init_repo(&[
+        ("src/main.rs", "fn main() {}"),
+        ("docs/README.md", "# docs"),
+    ]);
+    let config_dir = TempDir::new().expect("failed to create temp dir");
+    let config_path = config_dir.path().join("custom.yaml");
+    fs::write(
+        &config_path,
+        r#"
+checks:
+  - name: tests
+    when:
+      paths_changed: "src/*.rs"
+    then:
+      ensure_commands:
+        - cargo test
+"#,
+    )
+    .expect("failed to write config");
+    let transcript = write_transcript(
+        config_dir.path(),
+        &[(
+            "Edit",
+            serde_json::json!({"file_path": repo.path().join("src/main.rs")}),
+        )],
+    );
+    let input = stop_input(
+        "rufio-config-env-override-from-subdirectory",
+        &repo.path().join("docs"),
+        &transcript,
+    );
+
+    let config_env = config_path.to_string_lossy().to_string();
+    let (stdout, stderr, code) =
+        run_rufio_with_env(&[], &[("RUFIO_CONFIG", config_env.as_str())], &input);
+    assert_eq!(code, 0, "stderr: {stderr}");
+    let decision: serde_json::Value = serde_json::from_str(&stdout).expect("invalid JSON");
+    assert_eq!(decision["decision"], "block");
+    assert!(decision["reason"]
+        .as_str()
+        .expect("reason should be a string")
+        .contains("cargo test"));
+}
+
+#[test]
+fn test_warn_level_failure_is_system_message() {
+    let config = r#"
+checks:
+  - name: changelog
+    level: warn
+    when:
+      paths_changed: "**/*.rs"
+    then:
+      ensure_changed:
+        - CHANGELOG.md
+"#;
+    let repo = init_repo(&[
+        ("rufio-hooks.yaml", config),
+        ("src/main.rs", "fn main() {}"),
+    ]);
+
+    let (stdout, stderr, code) = run_rufio(&stop_input(
+        "warn-level-failure-is-system-message",
+        repo.path(),
+        "/nonexistent",
+    ));
+    assert_eq!(code, 0, "stderr: {stderr}");
+    let output: serde_json::Value = serde_json::from_str(&stdout).expect("invalid JSON");
+    let output = output.as_object().expect("expected an object");
+    assert_eq!(output.len(), 1, "unexpected keys: {output:?}");
+    assert!(output["systemMessage"]
+        .as_str()
+        .expect("systemMessage should be a string")
+        .contains("CHANGELOG.md"));
+}
+
+#[test]
+fn test_post_tool_use_check_is_system_message() {
+    let config = r#"
+checks:
+  - name: version
+    on: [post_tool_use]
+    when:
+      paths_changed: "**/*.rs"
+    then:
+      ensure_changed:
+        - version.toml
+"#;
+    let repo = init_repo(&[
+        ("rufio-hooks.yaml", config),
+        ("src/main.rs", "fn main() {}"),
+    ]);
+    let edit = serde_json::json!({
+        "hook_event_name": "PostToolUse",
+        "cwd": repo.path(),
+        "session_id": "post-tool-use-check-is-system-message",
+        "transcript_path": "/nonexistent",
+        "tool_name": "Edit",
+        "tool_input": {"file_path": repo.path().join("src/main.rs")},
+    });
+
+    let (stdout, stderr, code) = run_rufio(&edit.to_string());
+    assert_eq!(code, 0, "stderr: {stderr}");
+    let output: serde_json::Value = serde_json::from_str(&stdout).expect("invalid JSON");
+    let output = output.as_object().expect("expected an object");
+    assert_eq!(output.len(), 1, "unexpected keys: {output:?}");
+    assert!(output["systemMessage"]
+        .as_str()
+        .expect("systemMessage should be a string")
+        .contains("version.toml"));
+
+    // The check isn't tagged for Stop, so Stop has nothing to say
+    let (stdout, stderr, code) = run_rufio(&stop_input(
+        "post-tool-use-check-is-system-message",
+        repo.path(),
+        "/nonexistent",
+    ));
+    assert_eq!(code, 0, "stderr: {stderr}");
+    assert!(stdout.is_empty(), "stdout: {stdout}");
+}
+
+#[test]
+fn test_stop_from_subdirectory() {
+    // git reports paths from the repo root, not from cwd
+    let repo = init_repo(&[
+        (
+            "rufio-hooks.yaml",
+            r#"
+checks:
+  - name: tests
+    when:
+      paths_changed: "src/*.rs"
+    then:
+      ensure_commands:
+        - cargo test
+"#,
+        ),
+        ("src/main.rs", "fn main() {}"),
+    ]);
+    let transcript_dir = TempDir::new().expect("failed to create temp dir");
+    let main_rs = repo.path().join("src/main.rs");
+    let cwd = repo.path().join("src");
+
+    let transcript = write_transcript(
+        transcript_dir.path(),
+        &[("Edit", serde_json::json!({"file_path": main_rs}))],
+    );
+    let (stdout, stderr, code) =
+        run_rufio(&stop_input("stop-from-subdirectory", &cwd, &transcript));
+    assert_eq!(code, 0, "stderr: {stderr}");
+    assert!(stdout.contains("cargo test"), "stdout: {stdout}");
+
+    let transcript = write_transcript(
+        transcript_dir.path(),
+        &[
+            ("Edit", serde_json::json!({"file_path": main_rs})),
+            ("Bash", serde_json::json!({"command": "cargo test"})),
+        ],
+    );
+    let (stdout, stderr, code) =
+        run_rufio(&stop_input("stop-from-subdirectory", &cwd, &transcript));
+    assert_eq!(code, 0, "stderr: {stderr}");
+    assert!(stdout.is_empty(), "stdout: {stdout}");
+}
+
+#[test]
+fn test_each_subproject_uses_its_own_config() {
+    let rust_config = r#"
+checks:
+  - name: rust-tests
+    when:
+      paths_changed: "**/*.rs"
+    then:
+      ensure_commands:
+        - cargo test
+"#;
+    let ts_config = r#"
+checks:
+  - name: ts-tests
+    when:
+      paths_changed: "**/*.ts"
+    then:
+      ensure_commands:
+        - pnpm test
+  - name: ts-no-rust
+    when:
+      paths_changed: "**/*.rs"
+    then:
+      ensure_commands:
+        - never run
+"#;
+    let repo = init_repo(&[
+        ("crates/core/rufio-hooks.yaml", rust_config),
+        ("crates/core/src/lib.rs", "pub fn f() {}"),
+        ("packages/web/rufio-hooks.yaml", ts_config),
+        ("packages/web/src/index.ts", "export {};"),
+    ]);
+    let transcript_dir = TempDir::new().expect("failed to create temp dir");
+    let transcript = write_transcript(
+        transcript_dir.path(),
+        &[
+            (
+                "Edit",
+                serde_json::json!({"file_path": repo.path().join("crates/core/src/lib.rs")}),
+            ),
+            (
+                "Edit",
+                serde_json::json!({"file_path": repo.path().join("packages/web/src/index.ts")}),
+            ),
+        ],
+    );
+
+    let (stdout, stderr, code) = run_rufio(&stop_input(
+        "each-subproject-uses-its-own-config",
+        repo.path(),
+        &transcript,
+    ));
+    assert_eq!(code, 0, "stderr: {stderr}");
+    let decision: serde_json::Value = serde_json::from_str(&stdout).expect("invalid JSON");
+    assert_eq!(decision["decision"], "block");
+    let reason = decision["reason"]
+        .as_str()
+        .expect("reason should be a string");
+    assert!(reason.contains("rust-tests"), "reason: {reason}");
+    assert!(reason.contains("ts-tests"), "reason: {reason}");
+    // The .rs file belongs to the Rust crate's config only
+    assert!(!reason.contains("ts-no-rust"), "reason: {reason}");
+}
+
+#[test]
+fn test_version() {
+    for arg in ["version", "--version"] {
+        // Empty stdin: reading a hook input would fail
+        let (stdout, stderr, code) = run_rufio_with_args(&[arg], "");
+        assert_eq!(code, 0, "stderr: {stderr}");
+        assert!(
+            stdout.starts_with(&format!("rufio {}", env!("RUFIO_VERSION"))),
+            "stdout: {stdout}"
+        );
+        // The release version, not Cargo.toml's placeholder
+        assert!(!stdout.contains("0.0.0-dev"), "stdout: {stdout}");
+    }
+}
+
+#[test]
+fn test_init_writes_loadable_config() {
+    let repo = init_repo(&[("Cargo.toml", "[package]\nname = \"demo\"\n")]);
+
+    let output = Command::new(env!("CARGO_BIN_EXE_rufio"))
+        .arg("init")
+        .current_dir(repo.path())
+        .output()
+        .expect("failed to run rufio init");
+    assert!(output.status.success());
+    let config_path = repo.path().join("rufio-hooks.yaml");
+    assert!(String::from_utf8_lossy(&output.stdout).contains("rufio-hooks.yaml"));
+    assert!(config_path.exists());
+
+    // The generated config must load: check fails with an error if it doesn't
+    let config_arg = config_path.to_string_lossy().to_string();
+    let (stdout, stderr, code) = run_rufio_with_args(
+        &["check", "--config", &config_arg],
+        &stop_input(
+            "init-writes-loadable-config",
+            repo.path(),
+            "/nonexistent/transcript.jsonl",
+        ),
+    );
+    assert_eq!(code, 0, "stderr: {stderr}");
+    assert!(stdout.contains("PASS cargo-test"), "stdout: {stdout}");
+
+    // A second init must refuse to overwrite
+    let output = Command::new(env!("CARGO_BIN_EXE_rufio"))
+        .arg("init")
+        .current_dir(repo.path())
+        .output()
+        .expect("failed to run rufio init");
+    assert!(!output.status.success());
+}
+
+#[test]
+fn test_explain_lists_triggering_checks() {
+    let docs_check = r#"
+  - name: docs
+    when:
+      paths_changed: "*.md"
+    then:
+      ensure_commands:
+        - mdformat .
+"#;
+    let config = format!("{MIXED_CONFIG}{docs_check}");
+    let repo = init_repo(&[
+        ("rufio-hooks.yaml", config.as_str()),
+        ("src/main.rs", "fn main() {}"),
+    ]);
+
+    let output = isolated(&mut Command::new(env!("CARGO_BIN_EXE_rufio")))
+        .args(["explain", "src/main.rs"])
+        .current_dir(repo.path())
+        .output()
+        .expect("failed to run rufio explain");
+    assert!(output.status.success());
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("tests: triggers (run after editing: cargo test)"),
+        "stdout: {stdout}"
+    );
+    assert!(
+        stdout.contains("version: triggers (change one of: version.toml)"),
+        "stdout: {stdout}"
+    );
+    assert!(stdout.contains("docs: not triggered"), "stdout: {stdout}");
+}
+
 #[test]
 fn test_stop_event_no_package_nix() {
     // Use a temp directory without package.nix
-    let json =
-        r#"{"hook_event_name":"Stop","cwd":"/tmp","session_id":"test","transcript_path":"/tmp/t"}"#;
+    let json = r#"{"hook_event_name":"Stop","cwd":"/tmp","session_id":"stop-event-no-package-nix","transcript_path":"/tmp/t"}"#;
     let (stdout, _stderr, code) = run_rufio(json);
 
     assert_eq!(code, 0);
@@ -41,13 +651,25 @@ fn test_stop_event_no_package_nix() {
 
 #[test]
 fn test_unknown_event_noop() {
-    let json = r#"{"hook_event_name":"Start","cwd":"/tmp","session_id":"test","transcript_path":"/tmp/t"}"#;
+    let json = r#"{"hook_event_name":"Start","cwd":"/tmp","session_id":"unknown-event-noop","transcript_path":"/tmp/t"}"#;
     let (stdout, _stderr, code) = run_rufio(json);
 
     assert_eq!(code, 0);
     assert!(stdout.is_empty(), "Unknown events should be no-op");
 }
 
+#[test]
+fn test_notification_event() {
+    let json = r#"{"hook_event_name":"Notification","cwd":"/tmp","session_id":"notification-event","transcript_path":"/tmp/t","message":"Claude is waiting for your input"}"#;
+    let (stdout, _stderr, code) = run_rufio(json);
+
+    assert_eq!(code, 0);
+    assert!(
+        stdout.is_empty(),
+        "Notification must not produce hook output"
+    );
+}
+
 #[test]
 fn test_invalid_json_fails() {
     let json = "not valid json";
@@ -59,7 +681,7 @@ fn test_invalid_json_fails() {
 #[test]
 fn test_stop_hook_active_short_circuits() {
     // Even in a repo that would normally block, stop_hook_active=true must skip checks.
-    let json = r#"{"hook_event_name":"Stop","cwd":".","session_id":"test","transcript_path":"/tmp/t","stop_hook_active":true}"#;
+    let json = r#"{"hook_event_name":"Stop","cwd":".","session_id":"stop-hook-active-short-circuits","transcript_path":"/tmp/t","stop_hook_active":true}"#;
     let (stdout, _stderr, code) = run_rufio(json);
 
     assert_eq!(code, 0);